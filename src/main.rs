@@ -1,7 +1,13 @@
-use std::fs::File;
+use std::{fs::File, path::PathBuf};
 
 use serde::Deserialize;
 use tracing_subscriber::fmt::format::FmtSpan;
+use ui::{
+    cache::Cache,
+    export::ExportFilter,
+    gtfs::{FeedConfig, Mode, TransitData},
+    realtime::RealtimeSource,
+};
 
 mod ui;
 
@@ -12,6 +18,18 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 struct Config {
     tmdb_token: String,
     mapbox_token: String,
+    #[serde(default = "ui::gtfs::default_feed_sources")]
+    gtfs_sources: Vec<FeedConfig>,
+    #[serde(default)]
+    agencies: Option<Vec<String>>,
+    #[serde(default = "ui::gtfs::default_modes")]
+    modes: Vec<Mode>,
+    #[serde(default = "ui::cache::default_cache_dir")]
+    cache_dir: PathBuf,
+    realtime_source: Option<RealtimeSource>,
+    #[serde(default)]
+    realtime_namespace: Option<String>,
+    realtime_interval_secs: Option<u64>,
 }
 
 fn main() -> eframe::Result {
@@ -30,6 +48,13 @@ fn main() -> eframe::Result {
 
     let config_file = File::open("config.json").unwrap();
     let config: Config = serde_json::from_reader(config_file).unwrap();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "export") {
+        export(config, &args[1..]);
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions::default();
 
     eframe::run_native(
@@ -41,3 +66,37 @@ fn main() -> eframe::Result {
         }),
     )
 }
+
+// Writes a filtered subset of the configured feeds without starting the UI.
+fn export(config: Config, args: &[String]) {
+    let (path, filter) = match ExportFilter::from_args(args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{}", ui::export::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let cached = Cache::new(config.cache_dir).transit_data();
+    let transit_data = rt
+        .block_on(async {
+            match &cached {
+                Some(cached) => TransitData::load_if_changed(&config.gtfs_sources, cached).await,
+                None => Some(TransitData::load(&config.gtfs_sources).await),
+            }
+        })
+        .or(cached)
+        .unwrap();
+
+    match ui::export::export(&transit_data, &filter, &path) {
+        Ok(summary) => println!("wrote {}: {summary}", path.display()),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
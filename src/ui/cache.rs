@@ -0,0 +1,80 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use chrono::NaiveDate;
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{info, warn};
+
+use crate::ui::{gtfs::TransitData, state::Route};
+
+const TRANSIT_DATA_FILE: &str = "transit_data.bin";
+const ROUTES_FILE: &str = "routes.bin";
+
+pub fn default_cache_dir() -> PathBuf {
+    PathBuf::from("cache")
+}
+
+#[derive(Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub fn transit_data(&self) -> Option<TransitData> {
+        let mut transit_data: TransitData = self.read(TRANSIT_DATA_FILE)?;
+        transit_data.build_index();
+        Some(transit_data)
+    }
+
+    // Routes are computed from the transit data, so cached routes are dropped with it.
+    #[tracing::instrument(skip_all)]
+    pub fn store_transit_data(&self, transit_data: &TransitData) {
+        let _ = std::fs::remove_file(self.dir.join(ROUTES_FILE));
+        self.write(TRANSIT_DATA_FILE, transit_data);
+    }
+
+    pub fn routes(&self, service_date: NaiveDate) -> Option<Vec<Route>> {
+        let (cached_date, routes): (NaiveDate, Vec<Route>) = self.read(ROUTES_FILE)?;
+        (cached_date == service_date).then_some(routes)
+    }
+
+    #[tracing::instrument(skip(self, routes))]
+    pub fn store_routes(&self, service_date: NaiveDate, routes: &[Route]) {
+        self.write(ROUTES_FILE, &(service_date, routes));
+    }
+
+    fn read<T: DeserializeOwned>(&self, file: &str) -> Option<T> {
+        let path = self.dir.join(file);
+        let reader = BufReader::new(File::open(&path).ok()?);
+        match bincode::deserialize_from(reader) {
+            Ok(value) => {
+                info!("loaded {}", path.display());
+                Some(value)
+            }
+            Err(err) => {
+                warn!("failed to read {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    fn write<T: Serialize>(&self, file: &str, value: &T) {
+        let path = self.dir.join(file);
+        let result = std::fs::create_dir_all(&self.dir)
+            .map_err(bincode::Error::from)
+            .and_then(|_| File::create(&path).map_err(bincode::Error::from))
+            .and_then(|f| bincode::serialize_into(BufWriter::new(f), value));
+
+        if let Err(err) = result {
+            warn!("failed to write {}: {err}", path.display());
+        }
+    }
+}
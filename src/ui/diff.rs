@@ -0,0 +1,386 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::ui::gtfs::{Route, TransitData};
+
+pub const DEFAULT_MOVED_DISTANCE: f64 = 50.0;
+
+pub struct DiffOptions {
+    // Stops that moved less than this, in metres, are not reported.
+    pub moved_distance: f64,
+    // Trips per day are compared for `days` days from `from`.
+    pub from: NaiveDate,
+    pub days: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FeedDiff {
+    pub old: String,
+    pub new: String,
+    pub added_routes: Vec<RouteSummary>,
+    pub removed_routes: Vec<RouteSummary>,
+    pub moved_stops: Vec<MovedStop>,
+    pub renamed_stops: Vec<RenamedStop>,
+    pub changed_patterns: Vec<PatternChange>,
+    pub trip_counts: Vec<TripCountChange>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RouteSummary {
+    pub route_id: String,
+    pub name: String,
+}
+
+impl From<&Route> for RouteSummary {
+    fn from(route: &Route) -> Self {
+        Self {
+            route_id: route.route_id.clone(),
+            name: route.route_short_name.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MovedStop {
+    pub stop_id: String,
+    pub name: String,
+    pub distance: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RenamedStop {
+    pub stop_id: String,
+    pub old_name: String,
+    pub new_name: String,
+}
+
+// Stop sequences, as station names, that only one of the feeds has for a route.
+#[derive(Serialize, Clone, Debug)]
+pub struct PatternChange {
+    pub route: RouteSummary,
+    pub removed: Vec<Vec<String>>,
+    pub added: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TripCountChange {
+    pub date: NaiveDate,
+    pub route: RouteSummary,
+    pub old: usize,
+    pub new: usize,
+}
+
+impl FeedDiff {
+    pub fn new(old: &TransitData, new: &TransitData, options: &DiffOptions) -> Self {
+        let added_routes = new
+            .routes
+            .iter()
+            .filter(|r| old.route(&r.route_id).is_none())
+            .map(RouteSummary::from)
+            .collect();
+        let removed_routes = old
+            .routes
+            .iter()
+            .filter(|r| new.route(&r.route_id).is_none())
+            .map(RouteSummary::from)
+            .collect();
+
+        let mut moved_stops = Vec::new();
+        let mut renamed_stops = Vec::new();
+        for stop in &new.stops {
+            let Some(old_stop) = old.stop(&stop.stop_id) else {
+                continue;
+            };
+
+            if let Some(distance) = old_stop.distance(stop)
+                && distance > options.moved_distance
+            {
+                moved_stops.push(MovedStop {
+                    stop_id: stop.stop_id.clone(),
+                    name: stop.name().to_string(),
+                    distance,
+                });
+            }
+            if old_stop.stop_name != stop.stop_name {
+                renamed_stops.push(RenamedStop {
+                    stop_id: stop.stop_id.clone(),
+                    old_name: old_stop.name().to_string(),
+                    new_name: stop.name().to_string(),
+                });
+            }
+        }
+        moved_stops.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+
+        let old_patterns = patterns(old);
+        let new_patterns = patterns(new);
+        let changed_patterns = new
+            .routes
+            .iter()
+            .filter_map(|route| {
+                let before = old_patterns.get(route.route_id.as_str())?;
+                let after = new_patterns.get(route.route_id.as_str())?;
+                let change = PatternChange {
+                    route: route.into(),
+                    removed: station_names(old, before.difference(after)),
+                    added: station_names(new, after.difference(before)),
+                };
+                (!change.removed.is_empty() || !change.added.is_empty()).then_some(change)
+            })
+            .collect();
+
+        let mut trip_counts = Vec::new();
+        for date in options.from.iter_days().take(options.days as usize) {
+            let old_counts = trip_counts_on(old, date);
+            let new_counts = trip_counts_on(new, date);
+            let route_ids: BTreeSet<&str> = old_counts
+                .keys()
+                .chain(new_counts.keys())
+                .copied()
+                .collect();
+
+            for route_id in route_ids {
+                let old_count = old_counts.get(route_id).copied().unwrap_or_default();
+                let new_count = new_counts.get(route_id).copied().unwrap_or_default();
+                if old_count == new_count {
+                    continue;
+                }
+
+                let route = match new.route(route_id).or_else(|| old.route(route_id)) {
+                    Some(route) => route.into(),
+                    // Trips can refer to routes that are missing from routes.txt.
+                    None => RouteSummary {
+                        route_id: route_id.to_string(),
+                        name: route_id.to_string(),
+                    },
+                };
+                trip_counts.push(TripCountChange {
+                    date,
+                    route,
+                    old: old_count,
+                    new: new_count,
+                });
+            }
+        }
+
+        Self {
+            old: feed_names(old),
+            new: feed_names(new),
+            added_routes,
+            removed_routes,
+            moved_stops,
+            renamed_stops,
+            changed_patterns,
+            trip_counts,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_routes.is_empty()
+            && self.removed_routes.is_empty()
+            && self.moved_stops.is_empty()
+            && self.renamed_stops.is_empty()
+            && self.changed_patterns.is_empty()
+            && self.trip_counts.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        writeln!(md, "# Feed diff\n\n{} → {}\n", self.old, self.new).unwrap();
+        if self.is_empty() {
+            writeln!(md, "No changes.").unwrap();
+            return md;
+        }
+
+        section(&mut md, "Added routes", &self.added_routes, |r| {
+            format!("{} (`{}`)", r.name, r.route_id)
+        });
+        section(&mut md, "Removed routes", &self.removed_routes, |r| {
+            format!("{} (`{}`)", r.name, r.route_id)
+        });
+        section(&mut md, "Moved stops", &self.moved_stops, |s| {
+            format!("{} (`{}`): {:.0} m", s.name, s.stop_id, s.distance)
+        });
+        section(&mut md, "Renamed stops", &self.renamed_stops, |s| {
+            format!("`{}`: {} → {}", s.stop_id, s.old_name, s.new_name)
+        });
+
+        if !self.changed_patterns.is_empty() {
+            writeln!(md, "## Changed stop sequences\n").unwrap();
+            for change in &self.changed_patterns {
+                writeln!(md, "### {}\n", change.route.name).unwrap();
+                for pattern in &change.removed {
+                    writeln!(md, "- removed: {}", pattern.join(" → ")).unwrap();
+                }
+                for pattern in &change.added {
+                    writeln!(md, "- added: {}", pattern.join(" → ")).unwrap();
+                }
+                md.push('\n');
+            }
+        }
+
+        if !self.trip_counts.is_empty() {
+            writeln!(md, "## Trips per day\n").unwrap();
+            writeln!(md, "| Date | Route | Old | New |").unwrap();
+            writeln!(md, "| --- | --- | ---: | ---: |").unwrap();
+            for change in &self.trip_counts {
+                writeln!(
+                    md,
+                    "| {} | {} | {} | {} |",
+                    change.date, change.route.name, change.old, change.new
+                )
+                .unwrap();
+            }
+        }
+
+        md
+    }
+}
+
+fn section<T>(md: &mut String, title: &str, items: &[T], line: impl Fn(&T) -> String) {
+    if items.is_empty() {
+        return;
+    }
+
+    writeln!(md, "## {title}\n").unwrap();
+    for item in items {
+        writeln!(md, "- {}", line(item)).unwrap();
+    }
+    md.push('\n');
+}
+
+fn feed_names(td: &TransitData) -> String {
+    td.feeds
+        .iter()
+        .map(|feed| feed.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Distinct stop sequences of every route, as station IDs so that renamed
+// stations do not count as changed sequences.
+fn patterns(td: &TransitData) -> HashMap<&str, BTreeSet<Vec<&str>>> {
+    let mut patterns: HashMap<&str, BTreeSet<Vec<&str>>> = HashMap::new();
+    for trip in &td.trips {
+        let stations = td
+            .trip_stop_times(&trip.trip_id)
+            .iter()
+            .filter_map(|st| td.station(&st.stop_id))
+            .map(|s| s.stop_id.as_str())
+            .collect();
+        patterns
+            .entry(trip.route_id.as_str())
+            .or_default()
+            .insert(stations);
+    }
+
+    patterns
+}
+
+fn station_names<'a>(
+    td: &TransitData,
+    patterns: impl Iterator<Item = &'a Vec<&'a str>>,
+) -> Vec<Vec<String>> {
+    patterns
+        .map(|stations| {
+            stations
+                .iter()
+                .map(|id| td.stop(id).unwrap().name().to_string())
+                .collect()
+        })
+        .collect()
+}
+
+// Runs per route on `date`, counting every run of frequency based trips.
+fn trip_counts_on(td: &TransitData, date: NaiveDate) -> BTreeMap<&str, usize> {
+    let service_day = td.service_day(date);
+    let mut counts = BTreeMap::new();
+    for trip in td.trips.iter().filter(|t| service_day.runs(t)) {
+        *counts.entry(trip.route_id.as_str()).or_default() +=
+            td.trip_instances(&trip.trip_id).len();
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::gtfs::{CalendarDate, FeedConfig, FeedSource, Trip};
+
+    #[test]
+    fn trips_of_unknown_routes_are_counted() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let old = TransitData::default();
+        let mut new = TransitData::default();
+        new.trips.push(Trip {
+            trip_id: "t".to_string(),
+            route_id: "missing".to_string(),
+            service_id: "s".to_string(),
+            trip_headsign: None,
+            direction_id: None,
+            shape_id: None,
+            wheelchair_accessible: 0,
+        });
+        new.calendar_dates.push(CalendarDate {
+            service_id: "s".to_string(),
+            date,
+            exception_type: 1,
+        });
+        new.build_index();
+
+        let options = DiffOptions {
+            moved_distance: DEFAULT_MOVED_DISTANCE,
+            from: date,
+            days: 1,
+        };
+        let diff = FeedDiff::new(&old, &new, &options);
+
+        assert_eq!(diff.trip_counts.len(), 1);
+        assert_eq!(diff.trip_counts[0].route.name, "missing");
+        assert_eq!(diff.trip_counts[0].new, 1);
+    }
+
+    #[test]
+    fn one_of_several_feeds_is_compared() {
+        let sources = [
+            FeedConfig {
+                source: FeedSource::Directory("feeds/sample".into()),
+                namespace: Some("a".to_string()),
+            },
+            FeedConfig {
+                source: FeedSource::Directory("feeds/sample".into()),
+                namespace: Some("b".to_string()),
+            },
+        ];
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let loaded = rt.block_on(TransitData::load(&sources));
+        let other = rt.block_on(TransitData::load(&sources[1..]));
+        let options = DiffOptions {
+            moved_distance: DEFAULT_MOVED_DISTANCE,
+            from: NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(),
+            days: 7,
+        };
+
+        // Against all loaded feeds the routes of the first one look removed.
+        assert!(
+            !FeedDiff::new(&loaded, &other, &options)
+                .removed_routes
+                .is_empty()
+        );
+
+        let old = loaded.feed(&sources[1]).unwrap();
+        assert_eq!(old.routes.len(), other.routes.len());
+        assert!(FeedDiff::new(&old, &other, &options).is_empty());
+    }
+}
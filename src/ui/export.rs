@@ -0,0 +1,649 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::ui::gtfs::{FeedRecords, GtfsError, LoadedFeed, Stop, StopTime, TransitData};
+
+pub const USAGE: &str = "usage: licht export <output.zip> [--route ID]... [--agency ID]... \
+[--bbox MIN_LAT,MIN_LON,MAX_LAT,MAX_LON] [--from YYYYMMDD --until YYYYMMDD]";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    pub fn new(a: (f64, f64), b: (f64, f64)) -> Self {
+        Self {
+            min_lat: a.0.min(b.0),
+            min_lon: a.1.min(b.1),
+            max_lat: a.0.max(b.0),
+            max_lon: a.1.max(b.1),
+        }
+    }
+
+    fn contains(&self, stop: &Stop) -> bool {
+        stop.position().is_some_and(|(lat, lon)| {
+            (self.min_lat..=self.max_lat).contains(&lat)
+                && (self.min_lon..=self.max_lon).contains(&lon)
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExportFilter {
+    // Route ids or short names. Empty means all routes, same for agencies.
+    pub routes: Vec<String>,
+    pub agencies: Vec<String>,
+    pub bounding_box: Option<BoundingBox>,
+    pub dates: Option<(NaiveDate, NaiveDate)>,
+}
+
+impl ExportFilter {
+    // Parses the arguments of the export command into the output path and the filter.
+    pub fn from_args(args: &[String]) -> Result<(PathBuf, Self), String> {
+        let mut path = None;
+        let mut filter = Self::default();
+        let (mut from, mut until) = (None, None);
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+
+            match arg.as_str() {
+                "--route" => filter.routes.push(value()?),
+                "--agency" => filter.agencies.push(value()?),
+                "--bbox" => {
+                    let bbox = value()?;
+                    let coordinates: Vec<f64> = bbox
+                        .split(',')
+                        .map(|c| c.trim().parse().ok())
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| format!("invalid bounding box: {bbox}"))?;
+                    let [min_lat, min_lon, max_lat, max_lon] = coordinates[..] else {
+                        return Err(format!("invalid bounding box: {bbox}"));
+                    };
+                    filter.bounding_box =
+                        Some(BoundingBox::new((min_lat, min_lon), (max_lat, max_lon)));
+                }
+                "--from" => from = Some(parse_date(&value()?)?),
+                "--until" => until = Some(parse_date(&value()?)?),
+                flag if flag.starts_with("--") => return Err(format!("unknown option: {flag}")),
+                _ if path.is_none() => path = Some(PathBuf::from(arg)),
+                _ => return Err(format!("unexpected argument: {arg}")),
+            }
+        }
+
+        filter.dates = match (from, until) {
+            (Some(from), Some(until)) if from <= until => Some((from, until)),
+            (Some(_), Some(_)) => return Err("--from must not be after --until".to_string()),
+            (None, None) => None,
+            _ => return Err("--from and --until must be given together".to_string()),
+        };
+
+        let path = path.ok_or_else(|| "missing output file".to_string())?;
+        Ok((path, filter))
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y%m%d").map_err(|err| format!("invalid date {date}: {err}"))
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportSummary {
+    pub routes: usize,
+    pub trips: usize,
+    pub stops: usize,
+}
+
+impl fmt::Display for ExportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} routes, {} trips, {} stops",
+            self.routes, self.trips, self.stops
+        )
+    }
+}
+
+pub fn export(
+    transit_data: &TransitData,
+    filter: &ExportFilter,
+    path: &Path,
+) -> Result<ExportSummary, GtfsError> {
+    let subset = subset(transit_data, filter);
+    if subset.trips.is_empty() {
+        return Err(GtfsError::File {
+            file: path.display().to_string(),
+            message: "no trips match the filter".to_string(),
+        });
+    }
+
+    write_zip(&subset, path)?;
+    Ok(ExportSummary {
+        routes: subset.routes.len(),
+        trips: subset.trips.len(),
+        stops: subset.stops.len(),
+    })
+}
+
+// Everything the filtered trips depend on, so that no record of the subset
+// refers to one that was left out.
+pub fn subset(td: &TransitData, filter: &ExportFilter) -> TransitData {
+    let service_days: Vec<_> = match filter.dates {
+        Some((from, until)) => from
+            .iter_days()
+            .take_while(|d| *d <= until)
+            .map(|d| td.service_day(d))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let route_ids: HashSet<&str> = td
+        .routes
+        .iter()
+        .filter(|r| {
+            filter.routes.is_empty()
+                || filter
+                    .routes
+                    .iter()
+                    .any(|id| *id == r.route_id || *id == r.route_short_name)
+        })
+        .filter(|r| filter.agencies.is_empty() || filter.agencies.contains(&r.agency_id))
+        .map(|r| r.route_id.as_str())
+        .collect();
+
+    // Trips are cut to the stops inside the bounding box and dropped if less
+    // than two stops are left.
+    let mut trips = Vec::new();
+    let mut trip_stop_times = Vec::new();
+    for trip in td.trips.iter().filter(|t| {
+        route_ids.contains(t.route_id.as_str())
+            && (filter.dates.is_none() || service_days.iter().any(|d| d.runs(t)))
+    }) {
+        let stop_times: Vec<&StopTime> = td
+            .trip_stop_times(&trip.trip_id)
+            .into_iter()
+            .filter(|st| {
+                filter
+                    .bounding_box
+                    .is_none_or(|b| td.stop(&st.stop_id).is_some_and(|s| b.contains(s)))
+            })
+            .collect();
+        let stop_times = trim_untimed(&stop_times);
+        if stop_times.len() < 2 {
+            continue;
+        }
+
+        trips.push(trip);
+        trip_stop_times.extend_from_slice(stop_times);
+    }
+
+    let trip_ids: HashSet<&str> = trips.iter().map(|t| t.trip_id.as_str()).collect();
+    let route_ids: HashSet<&str> = trips.iter().map(|t| t.route_id.as_str()).collect();
+    let service_ids: HashSet<&str> = trips.iter().map(|t| t.service_id.as_str()).collect();
+    let shape_ids: HashSet<&str> = trips.iter().filter_map(|t| t.shape_id.as_deref()).collect();
+
+    let mut subset = TransitData::default();
+    subset.trips = trips.into_iter().cloned().collect();
+    subset.stop_times = trip_stop_times.iter().map(|st| (*st).clone()).collect();
+
+    subset.routes = td
+        .routes
+        .iter()
+        .filter(|r| route_ids.contains(r.route_id.as_str()))
+        .cloned()
+        .collect();
+    let agency_ids: HashSet<&str> = td
+        .routes
+        .iter()
+        .filter(|r| route_ids.contains(r.route_id.as_str()))
+        .map(|r| r.agency_id.as_str())
+        .collect();
+    subset.agencies = td
+        .agencies
+        .iter()
+        .filter(|a| agency_ids.contains(a.agency_id.as_str()))
+        .cloned()
+        .collect();
+
+    // Stops of the trips with their stations, and the entrances and nodes of
+    // those stations so that pathways stay complete.
+    let mut stop_ids: HashSet<&str> = trip_stop_times
+        .iter()
+        .map(|st| st.stop_id.as_str())
+        .collect();
+    let stations: Vec<&str> = stop_ids
+        .iter()
+        .filter_map(|id| td.stop(id)?.parent_station.as_deref())
+        .collect();
+    stop_ids.extend(stations);
+    let station_parts: Vec<&str> = td
+        .stops
+        .iter()
+        .filter(|s| !s.is_station() && !s.is_platform())
+        .filter(|s| {
+            s.parent_station
+                .as_deref()
+                .is_some_and(|p| stop_ids.contains(p))
+        })
+        .map(|s| s.stop_id.as_str())
+        .collect();
+    stop_ids.extend(station_parts);
+
+    let kept_stops: Vec<usize> = (0..td.stops.len())
+        .filter(|i| stop_ids.contains(td.stops[*i].stop_id.as_str()))
+        .collect();
+    subset.stops = kept_stops
+        .iter()
+        .map(|i| td.stops[*i].clone())
+        .map(|mut s| {
+            if s.parent_station
+                .as_deref()
+                .is_some_and(|p| !stop_ids.contains(p))
+            {
+                s.parent_station = None;
+            }
+            s
+        })
+        .collect();
+    let level_ids: HashSet<&str> = kept_stops
+        .iter()
+        .map(|i| &td.stops[*i])
+        .filter_map(|s| s.level_id.as_deref())
+        .collect();
+    subset.levels = td
+        .levels
+        .iter()
+        .filter(|l| level_ids.contains(l.level_id.as_str()))
+        .cloned()
+        .collect();
+    subset.pathways = td
+        .pathways
+        .iter()
+        .filter(|p| {
+            stop_ids.contains(p.from_stop_id.as_str()) && stop_ids.contains(p.to_stop_id.as_str())
+        })
+        .cloned()
+        .collect();
+    subset.transfers = td
+        .transfers
+        .iter()
+        .filter(|t| {
+            stop_ids.contains(t.from_stop_id.as_str()) && stop_ids.contains(t.to_stop_id.as_str())
+        })
+        .cloned()
+        .collect();
+
+    subset.calendars = td
+        .calendars
+        .iter()
+        .filter(|c| service_ids.contains(c.service_id.as_str()))
+        .cloned()
+        .filter_map(|mut c| {
+            if let Some((from, until)) = filter.dates {
+                c.start_date = c.start_date.max(from);
+                c.end_date = c.end_date.min(until);
+            }
+            (c.start_date <= c.end_date).then_some(c)
+        })
+        .collect();
+    subset.calendar_dates = td
+        .calendar_dates
+        .iter()
+        .filter(|d| service_ids.contains(d.service_id.as_str()))
+        .filter(|d| {
+            filter
+                .dates
+                .is_none_or(|(from, until)| from <= d.date && d.date <= until)
+        })
+        .cloned()
+        .collect();
+
+    subset.shapes = td
+        .shapes
+        .iter()
+        .filter(|s| shape_ids.contains(s.shape_id.as_str()))
+        .cloned()
+        .collect();
+    subset.frequencies = td
+        .frequencies
+        .iter()
+        .filter(|f| trip_ids.contains(f.trip_id.as_str()))
+        .cloned()
+        .collect();
+
+    fares_subset(td, &mut subset, &stop_ids);
+
+    subset.feeds = td
+        .feeds
+        .iter()
+        .map(|feed| {
+            let mut info = feed.info.clone();
+            if let (Some(info), Some((from, until))) = (&mut info, filter.dates) {
+                info.feed_start_date = Some(info.feed_start_date.map_or(from, |d| d.max(from)));
+                info.feed_end_date = Some(info.feed_end_date.map_or(until, |d| d.min(until)));
+            }
+            let stops = &feed.records.stops;
+            // The subset is only written, so only the stops are kept apart by feed.
+            let records = FeedRecords {
+                stops: kept_stops.partition_point(|i| *i < stops.start)
+                    ..kept_stops.partition_point(|i| *i < stops.end),
+                ..Default::default()
+            };
+            LoadedFeed {
+                source: feed.source.clone(),
+                records,
+                info,
+                valid_from: feed.valid_from,
+                valid_until: feed.valid_until,
+            }
+        })
+        .collect();
+
+    subset
+}
+
+// The first and last stop of a trip need times, which stops in between may not
+// have. Cutting a trip can leave such stops at its ends.
+fn trim_untimed<'a, 'b>(stop_times: &'a [&'b StopTime]) -> &'a [&'b StopTime] {
+    let start = stop_times
+        .iter()
+        .position(|st| st.departure_time.is_some())
+        .unwrap_or(stop_times.len());
+    let end = stop_times
+        .iter()
+        .rposition(|st| st.arrival_time.is_some())
+        .map_or(0, |i| i + 1);
+    stop_times.get(start..end).unwrap_or_default()
+}
+
+fn fares_subset(td: &TransitData, subset: &mut TransitData, stop_ids: &HashSet<&str>) {
+    let route_ids: HashSet<&str> = subset.routes.iter().map(|r| r.route_id.as_str()).collect();
+    let agency_ids: HashSet<&str> = subset
+        .agencies
+        .iter()
+        .map(|a| a.agency_id.as_str())
+        .collect();
+    let network_ids: HashSet<&str> = subset
+        .routes
+        .iter()
+        .filter_map(|r| r.network_id.as_deref())
+        .collect();
+
+    subset.fare_rules = td
+        .fare_rules
+        .iter()
+        .filter(|r| {
+            r.route_id
+                .as_deref()
+                .is_none_or(|id| route_ids.contains(id))
+        })
+        .cloned()
+        .collect();
+    // A fare without rules applies to every trip, so fares whose rules were all
+    // dropped are dropped as well.
+    subset.fare_attributes = td
+        .fare_attributes
+        .iter()
+        .filter(|a| {
+            a.agency_id
+                .as_deref()
+                .is_none_or(|id| agency_ids.contains(id))
+        })
+        .filter(|a| {
+            !td.fare_rules.iter().any(|r| r.fare_id == a.fare_id)
+                || subset.fare_rules.iter().any(|r| r.fare_id == a.fare_id)
+        })
+        .cloned()
+        .collect();
+    let fare_ids: HashSet<&str> = subset
+        .fare_attributes
+        .iter()
+        .map(|a| a.fare_id.as_str())
+        .collect();
+    subset
+        .fare_rules
+        .retain(|r| fare_ids.contains(r.fare_id.as_str()));
+
+    subset.stop_areas = td
+        .stop_areas
+        .iter()
+        .filter(|a| stop_ids.contains(a.stop_id.as_str()))
+        .cloned()
+        .collect();
+    let area_ids: HashSet<&str> = subset
+        .stop_areas
+        .iter()
+        .map(|a| a.area_id.as_str())
+        .collect();
+    subset.areas = td
+        .areas
+        .iter()
+        .filter(|a| area_ids.contains(a.area_id.as_str()))
+        .cloned()
+        .collect();
+    subset.fare_leg_rules = td
+        .fare_leg_rules
+        .iter()
+        .filter(|r| {
+            r.network_id
+                .as_deref()
+                .is_none_or(|id| network_ids.contains(id))
+        })
+        .filter(|r| {
+            [&r.from_area_id, &r.to_area_id]
+                .into_iter()
+                .all(|a| a.as_deref().is_none_or(|a| area_ids.contains(a)))
+        })
+        .cloned()
+        .collect();
+    let product_ids: HashSet<&str> = subset
+        .fare_leg_rules
+        .iter()
+        .map(|r| r.fare_product_id.as_str())
+        .collect();
+    subset.fare_products = td
+        .fare_products
+        .iter()
+        .filter(|p| product_ids.contains(p.fare_product_id.as_str()))
+        .cloned()
+        .collect();
+}
+
+pub fn write_zip(td: &TransitData, path: &Path) -> Result<(), GtfsError> {
+    let file = File::create(path).map_err(|err| write_error(&path.display().to_string(), err))?;
+    let mut zip = ZipWriter::new(file);
+
+    write_file(&mut zip, "agency.txt", &td.agencies)?;
+    write_file(&mut zip, "stops.txt", &td.stops)?;
+    write_file(&mut zip, "routes.txt", &td.routes)?;
+    write_file(&mut zip, "trips.txt", &td.trips)?;
+    write_file(&mut zip, "stop_times.txt", &td.stop_times)?;
+    write_file(&mut zip, "calendar.txt", &td.calendars)?;
+    write_file(&mut zip, "calendar_dates.txt", &td.calendar_dates)?;
+    write_file(&mut zip, "shapes.txt", &td.shapes)?;
+    write_file(&mut zip, "frequencies.txt", &td.frequencies)?;
+    write_file(&mut zip, "transfers.txt", &td.transfers)?;
+    write_file(&mut zip, "pathways.txt", &td.pathways)?;
+    write_file(&mut zip, "levels.txt", &td.levels)?;
+    write_file(&mut zip, "fare_attributes.txt", &td.fare_attributes)?;
+    write_file(&mut zip, "fare_rules.txt", &td.fare_rules)?;
+    write_file(&mut zip, "fare_products.txt", &td.fare_products)?;
+    write_file(&mut zip, "fare_leg_rules.txt", &td.fare_leg_rules)?;
+    write_file(&mut zip, "areas.txt", &td.areas)?;
+    write_file(&mut zip, "stop_areas.txt", &td.stop_areas)?;
+
+    // feed_info.txt has a single row, which only fits a subset of a single feed.
+    if let [feed] = &td.feeds[..]
+        && let Some(info) = &feed.info
+    {
+        write_file(&mut zip, "feed_info.txt", std::slice::from_ref(info))?;
+    }
+
+    zip.finish()
+        .map_err(|err| write_error(&path.display().to_string(), err))?;
+    Ok(())
+}
+
+// Files without records are left out.
+fn write_file<T: Serialize>(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    records: &[T],
+) -> Result<(), GtfsError> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    zip.start_file(name, SimpleFileOptions::default())
+        .map_err(|err| write_error(name, err))?;
+    let mut writer = csv::Writer::from_writer(zip);
+    for record in records {
+        writer
+            .serialize(record)
+            .map_err(|err| write_error(name, err))?;
+    }
+    writer.flush().map_err(|err| write_error(name, err))
+}
+
+fn write_error(file: &str, err: impl fmt::Display) -> GtfsError {
+    GtfsError::File {
+        file: file.to_string(),
+        message: format!("write failed: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::gtfs::{FeedConfig, FeedSource};
+
+    #[test]
+    fn subset_keeps_every_reference() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let td = rt.block_on(TransitData::load(&[FeedConfig {
+            source: FeedSource::Directory("feeds/sample".into()),
+            namespace: None,
+        }]));
+        let from = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let filter = ExportFilter {
+            routes: vec!["S1".to_string()],
+            dates: Some((from, from + chrono::Days::new(6))),
+            ..Default::default()
+        };
+        let subset = subset(&td, &filter);
+
+        assert!(!subset.trips.is_empty());
+        assert!(subset.trips.iter().all(|t| t.route_id == "S1"));
+
+        let agencies: HashSet<&str> = subset
+            .agencies
+            .iter()
+            .map(|a| a.agency_id.as_str())
+            .collect();
+        let routes: HashSet<&str> = subset.routes.iter().map(|r| r.route_id.as_str()).collect();
+        let trips: HashSet<&str> = subset.trips.iter().map(|t| t.trip_id.as_str()).collect();
+        let stops: HashSet<&str> = subset.stops.iter().map(|s| s.stop_id.as_str()).collect();
+        let levels: HashSet<&str> = subset.levels.iter().map(|l| l.level_id.as_str()).collect();
+        let shapes: HashSet<&str> = subset.shapes.iter().map(|s| s.shape_id.as_str()).collect();
+        let fares: HashSet<&str> = subset
+            .fare_attributes
+            .iter()
+            .map(|a| a.fare_id.as_str())
+            .collect();
+        let areas: HashSet<&str> = subset.areas.iter().map(|a| a.area_id.as_str()).collect();
+        let products: HashSet<&str> = subset
+            .fare_products
+            .iter()
+            .map(|p| p.fare_product_id.as_str())
+            .collect();
+        let services: HashSet<&str> = subset
+            .calendars
+            .iter()
+            .map(|c| c.service_id.as_str())
+            .chain(subset.calendar_dates.iter().map(|d| d.service_id.as_str()))
+            .collect();
+
+        assert!(
+            subset
+                .routes
+                .iter()
+                .all(|r| agencies.contains(r.agency_id.as_str()))
+        );
+        for trip in &subset.trips {
+            assert!(routes.contains(trip.route_id.as_str()));
+            assert!(services.contains(trip.service_id.as_str()));
+            assert!(trip.shape_id.as_deref().is_none_or(|s| shapes.contains(s)));
+        }
+        for stop_time in &subset.stop_times {
+            assert!(trips.contains(stop_time.trip_id.as_str()));
+            assert!(stops.contains(stop_time.stop_id.as_str()));
+        }
+        for stop in &subset.stops {
+            assert!(
+                stop.parent_station
+                    .as_deref()
+                    .is_none_or(|p| stops.contains(p))
+            );
+            assert!(stop.level_id.as_deref().is_none_or(|l| levels.contains(l)));
+        }
+        for (from, to) in subset
+            .pathways
+            .iter()
+            .map(|p| (&p.from_stop_id, &p.to_stop_id))
+            .chain(
+                subset
+                    .transfers
+                    .iter()
+                    .map(|t| (&t.from_stop_id, &t.to_stop_id)),
+            )
+        {
+            assert!(stops.contains(from.as_str()) && stops.contains(to.as_str()));
+        }
+        assert!(
+            subset
+                .frequencies
+                .iter()
+                .all(|f| trips.contains(f.trip_id.as_str()))
+        );
+        for rule in &subset.fare_rules {
+            assert!(fares.contains(rule.fare_id.as_str()));
+            assert!(rule.route_id.as_deref().is_none_or(|r| routes.contains(r)));
+        }
+        for area in &subset.stop_areas {
+            assert!(areas.contains(area.area_id.as_str()) && stops.contains(area.stop_id.as_str()));
+        }
+        assert!(
+            subset
+                .fare_leg_rules
+                .iter()
+                .all(|r| products.contains(r.fare_product_id.as_str()))
+        );
+
+        // The Hauptbahnhof platforms bring their station, its entrance and levels along,
+        // while stops only served by other routes stay out.
+        assert!(stops.contains("de:8212:5") && stops.contains("de:8212:5:E"));
+        assert_eq!(levels.len(), 2);
+        assert_eq!(subset.pathways.len(), 3);
+        assert!(!stops.contains("de:8212:1"));
+        assert!(!shapes.is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+use std::{collections::HashSet, fmt};
+
+use crate::ui::gtfs::{Route, TransitData};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fare {
+    pub name: String,
+    pub price: f64,
+    pub currency: String,
+}
+
+impl fmt::Display for Fare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:.2} {}", self.name, self.price, self.currency)
+    }
+}
+
+// Zone of a station, from the zone_id of the station or its platforms, or else
+// from the Fares v2 area it belongs to.
+pub fn zone(td: &TransitData, station_id: &str) -> Option<String> {
+    let stop_ids = td.stop_ids_at(station_id);
+    let mut zones: Vec<&str> = stop_ids
+        .iter()
+        .filter_map(|id| td.stop(id)?.zone_id.as_deref())
+        .filter(|z| !z.is_empty())
+        .collect();
+    zones.sort();
+    if let Some(zone) = zones.first() {
+        return Some(zone.to_string());
+    }
+
+    let area_id = td
+        .stop_areas
+        .iter()
+        .find(|a| stop_ids.contains(a.stop_id.as_str()))?
+        .area_id
+        .as_str();
+    let area = td.areas.iter().find(|a| a.area_id == area_id);
+    Some(
+        area.and_then(|a| a.area_name.clone())
+            .unwrap_or_else(|| area_id.to_string()),
+    )
+}
+
+// Fares for riding `route_id` from one station to another, cheapest first.
+// Both fare_rules.txt (v1) and fare_leg_rules.txt (v2) are considered.
+pub fn fares(td: &TransitData, route_id: &str, from: &str, to: &str) -> Vec<Fare> {
+    let Some(route) = td.route(route_id) else {
+        return Vec::new();
+    };
+
+    let mut fares = v1_fares(td, route, from, to);
+    fares.extend(v2_fares(td, route, from, to));
+    fares.sort_by(|a, b| a.price.total_cmp(&b.price));
+    // A fare matched by several rules is listed once, at its lowest price.
+    let mut names = HashSet::new();
+    fares.retain(|f| names.insert(f.name.clone()));
+    fares
+}
+
+fn zones_at<'a>(td: &'a TransitData, station_id: &'a str) -> HashSet<&'a str> {
+    td.stop_ids_at(station_id)
+        .into_iter()
+        .filter_map(|id| td.stop(id)?.zone_id.as_deref())
+        .collect()
+}
+
+fn areas_at<'a>(td: &'a TransitData, station_id: &'a str) -> HashSet<&'a str> {
+    let stop_ids = td.stop_ids_at(station_id);
+    td.stop_areas
+        .iter()
+        .filter(|a| stop_ids.contains(a.stop_id.as_str()))
+        .map(|a| a.area_id.as_str())
+        .collect()
+}
+
+fn v1_fares(td: &TransitData, route: &Route, from: &str, to: &str) -> Vec<Fare> {
+    let origins = zones_at(td, from);
+    let destinations = zones_at(td, to);
+
+    td.fare_attributes
+        .iter()
+        .filter(|a| a.agency_id.as_ref().is_none_or(|id| *id == route.agency_id))
+        .filter(|a| {
+            let mut rules = td
+                .fare_rules
+                .iter()
+                .filter(|r| r.fare_id == a.fare_id)
+                .peekable();
+            // Fares without rules apply to every trip.
+            rules.peek().is_none()
+                || rules.any(|r| {
+                    r.route_id.as_ref().is_none_or(|id| *id == route.route_id)
+                        && r.origin_id.as_deref().is_none_or(|z| origins.contains(z))
+                        && r.destination_id
+                            .as_deref()
+                            .is_none_or(|z| destinations.contains(z))
+                })
+        })
+        .map(|a| Fare {
+            name: a.fare_id.clone(),
+            price: a.price,
+            currency: a.currency_type.clone(),
+        })
+        .collect()
+}
+
+fn v2_fares(td: &TransitData, route: &Route, from: &str, to: &str) -> Vec<Fare> {
+    let from_areas = areas_at(td, from);
+    let to_areas = areas_at(td, to);
+
+    td.fare_leg_rules
+        .iter()
+        .filter(|r| {
+            r.network_id
+                .as_ref()
+                .is_none_or(|id| route.network_id.as_ref() == Some(id))
+                && r.from_area_id
+                    .as_deref()
+                    .is_none_or(|a| from_areas.contains(a))
+                && r.to_area_id.as_deref().is_none_or(|a| to_areas.contains(a))
+        })
+        .flat_map(|r| {
+            td.fare_products
+                .iter()
+                .filter(|p| p.fare_product_id == r.fare_product_id)
+        })
+        .map(|p| Fare {
+            name: p
+                .fare_product_name
+                .clone()
+                .unwrap_or_else(|| p.fare_product_id.clone()),
+            price: p.amount,
+            currency: p.currency.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::gtfs::tests::read;
+
+    // Zone A is set on a platform of s1, s3 is only in a Fares v2 area and s4 is
+    // in no zone at all.
+    fn feed() -> TransitData {
+        let mut td = TransitData::default();
+        td.stops.extend(
+            read(
+                "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station,zone_id\n\
+                 s1,One,49.0,8.4,1,,\n\
+                 p1,One,49.0,8.4,0,s1,A\n\
+                 s2,Two,49.01,8.4,1,,B\n\
+                 s3,Three,49.02,8.4,1,,\n\
+                 s4,Four,49.03,8.4,1,,\n",
+            )
+            .0,
+        );
+        td.routes.extend(
+            read(
+                "route_id,route_short_name,route_type,network_id\n\
+                 r,1,0,net\n\
+                 r2,2,0,\n",
+            )
+            .0,
+        );
+        td.fare_attributes.extend(
+            read(
+                "fare_id,price,currency_type,payment_method,transfers\n\
+                 Single,2.5,EUR,0,\n\
+                 All,3.0,EUR,0,\n\
+                 Other,1.5,EUR,0,\n",
+            )
+            .0,
+        );
+        td.fare_rules.extend(
+            read(
+                "fare_id,route_id,origin_id,destination_id\n\
+                 Single,r,A,B\n\
+                 Other,r2,,\n",
+            )
+            .0,
+        );
+        td.areas
+            .extend(read("area_id,area_name\nnorth,Nord\nsouth,\n").0);
+        td.stop_areas
+            .extend(read("area_id,stop_id\nnorth,s3\nsouth,s2\n").0);
+        td.fare_products.extend(
+            read(
+                "fare_product_id,fare_product_name,amount,currency\n\
+                 single,Single,4.0,EUR\n\
+                 day,Day ticket,5.0,EUR\n",
+            )
+            .0,
+        );
+        td.fare_leg_rules.extend(
+            read(
+                "network_id,from_area_id,to_area_id,fare_product_id\n\
+                 net,,south,single\n\
+                 net,north,south,day\n",
+            )
+            .0,
+        );
+        td.build_index();
+        td
+    }
+
+    fn names(fares: Vec<Fare>) -> Vec<String> {
+        fares.into_iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn zones_come_from_platforms_or_areas() {
+        let td = feed();
+        assert_eq!(zone(&td, "s1").as_deref(), Some("A"));
+        assert_eq!(zone(&td, "s2").as_deref(), Some("B"));
+        assert_eq!(zone(&td, "s3").as_deref(), Some("Nord"));
+        assert_eq!(zone(&td, "s4"), None);
+    }
+
+    #[test]
+    fn v1_rules_match_route_and_zones() {
+        let td = feed();
+        let route = td.route("r").unwrap();
+        assert_eq!(
+            names(v1_fares(&td, route, "s1", "s2")),
+            ["Single 2.50 EUR", "All 3.00 EUR"]
+        );
+        // The zones are the wrong way round for the single fare.
+        assert_eq!(names(v1_fares(&td, route, "s2", "s1")), ["All 3.00 EUR"]);
+        let route = td.route("r2").unwrap();
+        assert_eq!(
+            names(v1_fares(&td, route, "s1", "s2")),
+            ["All 3.00 EUR", "Other 1.50 EUR"]
+        );
+    }
+
+    #[test]
+    fn v2_rules_match_network_and_areas() {
+        let td = feed();
+        let route = td.route("r").unwrap();
+        assert_eq!(
+            names(v2_fares(&td, route, "s3", "s2")),
+            ["Single 4.00 EUR", "Day ticket 5.00 EUR"]
+        );
+        assert_eq!(
+            names(v2_fares(&td, route, "s2", "s3")),
+            Vec::<String>::new()
+        );
+        // Leg rules of a network do not apply to routes outside of it.
+        let route = td.route("r2").unwrap();
+        assert!(v2_fares(&td, route, "s3", "s2").is_empty());
+    }
+
+    #[test]
+    fn fares_are_listed_once_at_their_lowest_price() {
+        let td = feed();
+        assert_eq!(
+            names(fares(&td, "r", "s1", "s2")),
+            ["Single 2.50 EUR", "All 3.00 EUR"]
+        );
+        assert_eq!(
+            names(fares(&td, "r", "s3", "s2")),
+            ["All 3.00 EUR", "Single 4.00 EUR", "Day ticket 5.00 EUR"]
+        );
+    }
+}
@@ -1,106 +1,2307 @@
-use std::io::Cursor;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::File,
+    io::{self, Cursor, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
 
 use bytes::Bytes;
-use serde::Deserialize;
-use zip::ZipArchive;
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use reqwest::{StatusCode, header};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned};
+use tracing::warn;
+use zip::{ZipArchive, result::ZipError};
 
-const VBK_AGENCY_ID: &str = "02";
-const ALBTAL_AGENCY_ID: &str = "01";
+use crate::ui::search::StopIndex;
 
-#[derive(Deserialize, Clone)]
+pub const KVV_FEED_URL: &str = "https://projekte.kvv-efa.de/GTFS/google_transit.zip";
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(from = "String")]
+pub enum FeedSource {
+    Url(String),
+    Zip(PathBuf),
+    Directory(PathBuf),
+}
+
+impl From<String> for FeedSource {
+    fn from(source: String) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            FeedSource::Url(source)
+        } else if source.ends_with(".zip") {
+            FeedSource::Zip(source.into())
+        } else {
+            FeedSource::Directory(source.into())
+        }
+    }
+}
+
+impl fmt::Display for FeedSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeedSource::Url(url) => write!(f, "{url}"),
+            FeedSource::Zip(path) | FeedSource::Directory(path) => {
+                write!(f, "{}", path.display())
+            }
+        }
+    }
+}
+
+// A feed from the config. IDs of namespaced feeds are prefixed with `<namespace>:`,
+// so that several feeds can be loaded without their IDs colliding.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(from = "FeedConfigEntry")]
+pub struct FeedConfig {
+    pub source: FeedSource,
+    pub namespace: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FeedConfigEntry {
+    Source(FeedSource),
+    Namespaced {
+        source: FeedSource,
+        namespace: Option<String>,
+    },
+}
+
+impl From<FeedConfigEntry> for FeedConfig {
+    fn from(entry: FeedConfigEntry) -> Self {
+        match entry {
+            FeedConfigEntry::Source(source) => FeedConfig {
+                source,
+                namespace: None,
+            },
+            FeedConfigEntry::Namespaced { source, namespace } => FeedConfig { source, namespace },
+        }
+    }
+}
+
+impl fmt::Display for FeedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.namespace {
+            Some(namespace) => write!(f, "{namespace}:{}", self.source),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
+pub fn default_feed_sources() -> Vec<FeedConfig> {
+    vec![FeedConfig {
+        source: FeedSource::Url(KVV_FEED_URL.to_string()),
+        namespace: None,
+    }]
+}
+
+enum Feed {
+    Zip(ZipArchive<Cursor<Bytes>>),
+    Directory(PathBuf),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct FeedVersion {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Feed {
+    // Returns `None` if the feed did not change since `previous`.
+    async fn open(
+        source: &FeedSource,
+        previous: Option<&FeedVersion>,
+    ) -> Result<Option<(Self, FeedVersion)>, GtfsError> {
+        match source {
+            FeedSource::Url(url) => {
+                let download_error = |err: reqwest::Error| GtfsError::Download {
+                    url: url.clone(),
+                    message: err.to_string(),
+                };
+
+                let Some((bytes, version)) =
+                    download(url, previous).await.map_err(download_error)?
+                else {
+                    return Ok(None);
+                };
+
+                Ok(Some((Self::zip(bytes, url)?, version)))
+            }
+            FeedSource::Zip(path) => {
+                let archive_error = |err: io::Error| GtfsError::Archive {
+                    source: path.display().to_string(),
+                    message: err.to_string(),
+                };
+
+                let version = local_version(path).map_err(archive_error)?;
+                if previous == Some(&version) {
+                    return Ok(None);
+                }
+
+                let bytes = std::fs::read(path).map_err(archive_error)?;
+                let feed = Self::zip(Bytes::from(bytes), &path.display().to_string())?;
+                Ok(Some((feed, version)))
+            }
+            FeedSource::Directory(path) => {
+                let version = local_version(path).map_err(|err| GtfsError::Archive {
+                    source: path.display().to_string(),
+                    message: err.to_string(),
+                })?;
+                if previous == Some(&version) {
+                    return Ok(None);
+                }
+
+                Ok(Some((Feed::Directory(path.clone()), version)))
+            }
+        }
+    }
+
+    fn zip(bytes: Bytes, source: &str) -> Result<Self, GtfsError> {
+        ZipArchive::new(Cursor::new(bytes))
+            .map(Feed::Zip)
+            .map_err(|err| GtfsError::Archive {
+                source: source.to_string(),
+                message: err.to_string(),
+            })
+    }
+
+    fn file(&mut self, name: &str) -> Result<Option<Box<dyn Read + '_>>, GtfsError> {
+        let file_error = |message: String| GtfsError::File {
+            file: name.to_string(),
+            message,
+        };
+
+        match self {
+            Feed::Zip(zip) => match zip.by_name(name) {
+                Ok(file) => Ok(Some(Box::new(file))),
+                Err(ZipError::FileNotFound) => Ok(None),
+                Err(err) => Err(file_error(err.to_string())),
+            },
+            Feed::Directory(path) => match File::open(path.join(name)) {
+                Ok(file) => Ok(Some(Box::new(file))),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(file_error(err.to_string())),
+            },
+        }
+    }
+}
+
+async fn download(
+    url: &str,
+    previous: Option<&FeedVersion>,
+) -> Result<Option<(Bytes, FeedVersion)>, reqwest::Error> {
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(previous) = previous {
+        if let Some(etag) = &previous.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &previous.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let resp = request.send().await?.error_for_status()?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let version = FeedVersion {
+        etag: header(header::ETAG),
+        last_modified: header(header::LAST_MODIFIED),
+    };
+
+    Ok(Some((resp.bytes().await?, version)))
+}
+
+// Local feeds are versioned by the newest modification time of their files.
+fn local_version(path: &Path) -> io::Result<FeedVersion> {
+    let metadata = std::fs::metadata(path)?;
+    let mut modified = metadata.modified()?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            modified = modified.max(entry?.metadata()?.modified()?);
+        }
+    }
+
+    let seconds = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    Ok(FeedVersion {
+        etag: None,
+        last_modified: Some(seconds.to_string()),
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum GtfsError {
+    Download {
+        url: String,
+        message: String,
+    },
+    Archive {
+        source: String,
+        message: String,
+    },
+    MissingFile {
+        file: String,
+    },
+    File {
+        file: String,
+        message: String,
+    },
+    Row {
+        file: String,
+        line: Option<u64>,
+        field: Option<String>,
+        message: String,
+    },
+}
+
+impl GtfsError {
+    fn row(file: &str, err: &csv::Error, headers: Option<&csv::StringRecord>) -> Self {
+        let field = match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err
+                .field()
+                .and_then(|i| headers.and_then(|h| h.get(i as usize)))
+                .map(|f| f.to_string()),
+            _ => None,
+        };
+
+        let message = match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+            _ => err.to_string(),
+        };
+
+        GtfsError::Row {
+            file: file.to_string(),
+            line: err.position().map(|p| p.line()),
+            field,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for GtfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GtfsError::Download { url, message } => write!(f, "{url}: download failed: {message}"),
+            GtfsError::Archive { source, message } => write!(f, "{source}: {message}"),
+            GtfsError::MissingFile { file } => write!(f, "{file}: required file is missing"),
+            GtfsError::File { file, message } => write!(f, "{file}: {message}"),
+            GtfsError::Row {
+                file,
+                line,
+                field,
+                message,
+            } => {
+                write!(f, "{file}")?;
+                if let Some(line) = line {
+                    write!(f, ":{line}")?;
+                }
+                if let Some(field) = field {
+                    write!(f, " ({field})")?;
+                }
+                write!(f, ": {message}")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GtfsTime(pub u32);
+
+pub const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+impl GtfsTime {
+    pub fn hms(hours: u32, minutes: u32, seconds: u32) -> Self {
+        Self(hours * 3600 + minutes * 60 + seconds)
+    }
+}
+
+impl fmt::Display for GtfsTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hours = self.0 / 3600 % 24;
+        let minutes = self.0 / 60 % 60;
+        write!(f, "{hours:02}:{minutes:02}")
+    }
+}
+
+impl Serialize for GtfsTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hours = self.0 / 3600;
+        let minutes = self.0 / 60 % 60;
+        let seconds = self.0 % 60;
+        serializer.serialize_str(&format!("{hours:02}:{minutes:02}:{seconds:02}"))
+    }
+}
+
+impl<'de> Deserialize<'de> for GtfsTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let time = String::deserialize(deserializer)?;
+        let parts: Vec<u32> = time
+            .trim()
+            .split(':')
+            .map(|p| p.parse().map_err(serde::de::Error::custom))
+            .collect::<Result<_, _>>()?;
+
+        match parts[..] {
+            [hours, minutes, seconds] => Ok(GtfsTime::hms(hours, minutes, seconds)),
+            _ => Err(serde::de::Error::custom(format!("invalid time: {time}"))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct StopTime {
     pub trip_id: String,
+    pub arrival_time: Option<GtfsTime>,
+    pub departure_time: Option<GtfsTime>,
     pub stop_id: String,
+    pub stop_sequence: u32,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Route {
     pub route_id: String,
+    #[serde(default)]
     pub agency_id: String,
     pub route_short_name: String,
-    pub route_type: u8,
+    pub route_type: u16,
+    pub route_color: Option<String>,
+    pub route_text_color: Option<String>,
+    pub network_id: Option<String>,
+}
+
+impl Route {
+    pub fn mode(&self) -> Mode {
+        Mode::from_route_type(self.route_type)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Agency {
+    #[serde(default)]
+    pub agency_id: String,
+    pub agency_name: String,
+    #[serde(default)]
+    pub agency_url: String,
+    #[serde(default)]
+    pub agency_timezone: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Tram,
+    Subway,
+    Rail,
+    Bus,
+    Trolleybus,
+    Ferry,
+    CableTram,
+    AerialLift,
+    Funicular,
+    Monorail,
+    Other,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 11] = [
+        Mode::Tram,
+        Mode::Subway,
+        Mode::Rail,
+        Mode::Bus,
+        Mode::Trolleybus,
+        Mode::Ferry,
+        Mode::CableTram,
+        Mode::AerialLift,
+        Mode::Funicular,
+        Mode::Monorail,
+        Mode::Other,
+    ];
+
+    // Basic route types and the extended Hierarchical Vehicle Types
+    // https://developers.google.com/transit/gtfs/reference/extended-route-types
+    pub fn from_route_type(route_type: u16) -> Self {
+        match route_type {
+            0 | 900..=999 => Mode::Tram,
+            1 | 400..=402 | 500..=699 => Mode::Subway,
+            2 | 100..=199 | 300..=399 | 403..=404 => Mode::Rail,
+            3 | 200..=299 | 700..=799 => Mode::Bus,
+            11 | 800..=899 => Mode::Trolleybus,
+            4 | 1000..=1099 | 1200..=1299 => Mode::Ferry,
+            5 => Mode::CableTram,
+            6 | 1300..=1399 => Mode::AerialLift,
+            7 | 1400..=1499 => Mode::Funicular,
+            12 | 405 => Mode::Monorail,
+            _ => Mode::Other,
+        }
+    }
+
+    pub fn is_rail(&self) -> bool {
+        matches!(
+            self,
+            Mode::Tram
+                | Mode::Subway
+                | Mode::Rail
+                | Mode::CableTram
+                | Mode::Funicular
+                | Mode::Monorail
+        )
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Tram => "Tram",
+            Mode::Subway => "Subway",
+            Mode::Rail => "Rail",
+            Mode::Bus => "Bus",
+            Mode::Trolleybus => "Trolleybus",
+            Mode::Ferry => "Ferry",
+            Mode::CableTram => "Cable tram",
+            Mode::AerialLift => "Aerial lift",
+            Mode::Funicular => "Funicular",
+            Mode::Monorail => "Monorail",
+            Mode::Other => "Other",
+        }
+    }
+}
+
+pub fn default_modes() -> Vec<Mode> {
+    vec![Mode::Tram, Mode::Subway, Mode::Rail]
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Trip {
     pub trip_id: String,
     pub route_id: String,
+    pub service_id: String,
+    pub trip_headsign: Option<String>,
+    pub direction_id: Option<u8>,
+    pub shape_id: Option<String>,
+    #[serde(default, with = "empty_default")]
+    pub wheelchair_accessible: u8,
+}
+
+impl Trip {
+    pub fn is_accessible(&self) -> bool {
+        self.wheelchair_accessible == WHEELCHAIR_ACCESSIBLE
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Shape {
+    pub shape_id: String,
+    pub shape_pt_lat: f64,
+    pub shape_pt_lon: f64,
+    pub shape_pt_sequence: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Frequency {
+    pub trip_id: String,
+    pub start_time: GtfsTime,
+    pub end_time: GtfsTime,
+    pub headway_secs: u32,
+    #[serde(default, with = "empty_default")]
+    pub exact_times: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Transfer {
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    #[serde(default, with = "empty_default")]
+    pub transfer_type: u8,
+    pub min_transfer_time: Option<u32>,
+}
+
+pub const TRANSFER_NOT_POSSIBLE: u8 = 3;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Pathway {
+    pub pathway_id: String,
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    pub pathway_mode: u8,
+    pub is_bidirectional: u8,
+}
+
+const PATHWAY_STAIRS: u8 = 2;
+const PATHWAY_ESCALATOR: u8 = 4;
+
+impl Pathway {
+    fn is_step_free(&self) -> bool {
+        self.pathway_mode != PATHWAY_STAIRS && self.pathway_mode != PATHWAY_ESCALATOR
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Level {
+    pub level_id: String,
+    pub level_index: f64,
+    pub level_name: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Accessibility {
+    StepFree,
+    NotAccessible,
+    Unknown,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FareAttribute {
+    pub fare_id: String,
+    pub price: f64,
+    pub currency_type: String,
+    pub payment_method: u8,
+    // Empty for unlimited transfers.
+    pub transfers: Option<u8>,
+    pub agency_id: Option<String>,
+    pub transfer_duration: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FareRule {
+    pub fare_id: String,
+    pub route_id: Option<String>,
+    pub origin_id: Option<String>,
+    pub destination_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FareProduct {
+    pub fare_product_id: String,
+    pub fare_product_name: Option<String>,
+    pub amount: f64,
+    pub currency: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FareLegRule {
+    pub network_id: Option<String>,
+    pub from_area_id: Option<String>,
+    pub to_area_id: Option<String>,
+    pub fare_product_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Area {
+    pub area_id: String,
+    pub area_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StopArea {
+    pub area_id: String,
+    pub stop_id: String,
+}
+
+// One run of a trip. Trips from frequencies.txt are templates that are shifted
+// to every start time between `start_time` and `end_time`.
+#[derive(Clone, Copy, Debug)]
+pub struct TripInstance {
+    pub shift: i64,
+    pub headway: Option<u32>,
+}
+
+const SINGLE_INSTANCE: &[TripInstance] = &[TripInstance {
+    shift: 0,
+    headway: None,
+}];
+
+impl TripInstance {
+    pub fn apply(&self, time: GtfsTime) -> Option<GtfsTime> {
+        u32::try_from(time.0 as i64 + self.shift).ok().map(GtfsTime)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Stop {
     pub stop_id: String,
-    pub stop_name: String,
-    pub stop_lat: f64,
-    pub stop_lon: f64,
+    // Only optional for generic nodes and boarding areas.
+    pub stop_name: Option<String>,
+    pub stop_lat: Option<f64>,
+    pub stop_lon: Option<f64>,
+    #[serde(default, with = "empty_default")]
+    pub location_type: u8,
+    pub parent_station: Option<String>,
+    pub zone_id: Option<String>,
+    #[serde(default, with = "empty_default")]
+    pub wheelchair_boarding: u8,
+    pub level_id: Option<String>,
+}
+
+const LOCATION_TYPE_PLATFORM: u8 = 0;
+const LOCATION_TYPE_STATION: u8 = 1;
+const LOCATION_TYPE_ENTRANCE: u8 = 2;
+const WHEELCHAIR_ACCESSIBLE: u8 = 1;
+const WHEELCHAIR_INACCESSIBLE: u8 = 2;
+const EARTH_RADIUS: f64 = 6_371_000.0;
+// Stations of different feeds closer than this, in metres, can be linked.
+const STATION_LINK_DISTANCE: f64 = 50.0;
+// Boarding areas are two levels below their station, longer walks up the
+// parent stations only happen on cycles.
+const MAX_PARENT_DEPTH: usize = 2;
+// Roughly 1km, so that all stops within the search radius are in neighbouring cells.
+pub const GRID_CELL_SIZE: f64 = 0.01;
+
+impl Stop {
+    pub fn is_station(&self) -> bool {
+        self.location_type == LOCATION_TYPE_STATION
+    }
+
+    pub fn is_platform(&self) -> bool {
+        self.location_type == LOCATION_TYPE_PLATFORM
+    }
+
+    pub fn name(&self) -> &str {
+        self.stop_name.as_deref().unwrap_or_default()
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        Some((self.stop_lat?, self.stop_lon?))
+    }
+
+    pub fn distance(&self, other: &Stop) -> Option<f64> {
+        let ((lat_a, lon_a), (lat_b, lon_b)) = (self.position()?, other.position()?);
+        Some(distance(lat_a, lon_a, lat_b, lon_b))
+    }
+
+    fn grid_cell(&self) -> Option<(i64, i64)> {
+        let (lat, lon) = self.position()?;
+        Some(grid_cell(lat, lon))
+    }
+}
+
+// Great circle distance in metres.
+pub fn distance(lat_a: f64, lon_a: f64, lat_b: f64, lon_b: f64) -> f64 {
+    let d_lon = (lon_b - lon_a).to_radians();
+    let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+    let d_lat = lat_b - lat_a;
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+pub fn grid_cell(lat: f64, lon: f64) -> (i64, i64) {
+    (
+        (lat / GRID_CELL_SIZE).floor() as i64,
+        (lon / GRID_CELL_SIZE).floor() as i64,
+    )
+}
+
+// All ordered pairs of stops that are at most `radius` metres apart, with their distance.
+// `radius` must be smaller than the grid cells. Stops without a location are skipped.
+pub fn nearby_stops(stops: &[&Stop], radius: f64) -> Vec<(usize, usize, f64)> {
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, stop) in stops.iter().enumerate() {
+        if let Some(cell) = stop.grid_cell() {
+            grid.entry(cell).or_default().push(i);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for (i, stop) in stops.iter().enumerate() {
+        let Some((lat, lon)) = stop.grid_cell() else {
+            continue;
+        };
+        for cell in
+            (lat - 1..=lat + 1).flat_map(|lat| (lon - 1..=lon + 1).map(move |lon| (lat, lon)))
+        {
+            for j in grid.get(&cell).into_iter().flatten() {
+                let distance = stop.distance(stops[*j]).unwrap();
+                if *j != i && distance <= radius {
+                    pairs.push((i, *j, distance));
+                }
+            }
+        }
+    }
+
+    pairs
+}
+
+// Names match if most of their words match, ignoring case and common abbreviations.
+fn similar_names(a: &str, b: &str) -> bool {
+    let words = |name: &str| -> HashSet<String> {
+        name.to_lowercase()
+            .replace('ß', "ss")
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| match w {
+                "hbf" => "hauptbahnhof".to_string(),
+                "bf" | "bhf" => "bahnhof".to_string(),
+                "str" => "strasse".to_string(),
+                "pl" => "platz".to_string(),
+                w => w.to_string(),
+            })
+            .collect()
+    };
+
+    let (a, b) = (words(a), words(b));
+    let common = a.intersection(&b).count();
+    let union = a.union(&b).count();
+    common > 0 && (common == a.len().min(b.len()) || common * 2 >= union)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Calendar {
+    pub service_id: String,
+    pub monday: u8,
+    pub tuesday: u8,
+    pub wednesday: u8,
+    pub thursday: u8,
+    pub friday: u8,
+    pub saturday: u8,
+    pub sunday: u8,
+    #[serde(with = "gtfs_date")]
+    pub start_date: NaiveDate,
+    #[serde(with = "gtfs_date")]
+    pub end_date: NaiveDate,
+}
+
+impl Calendar {
+    fn runs_on(&self, date: NaiveDate) -> bool {
+        let day = match date.weekday() {
+            Weekday::Mon => self.monday,
+            Weekday::Tue => self.tuesday,
+            Weekday::Wed => self.wednesday,
+            Weekday::Thu => self.thursday,
+            Weekday::Fri => self.friday,
+            Weekday::Sat => self.saturday,
+            Weekday::Sun => self.sunday,
+        };
+
+        day == 1 && self.start_date <= date && date <= self.end_date
+    }
+}
+
+const SERVICE_ADDED: u8 = 1;
+const SERVICE_REMOVED: u8 = 2;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FeedInfo {
+    pub feed_publisher_name: String,
+    pub feed_publisher_url: String,
+    pub feed_lang: String,
+    #[serde(default, with = "gtfs_date::optional")]
+    pub feed_start_date: Option<NaiveDate>,
+    #[serde(default, with = "gtfs_date::optional")]
+    pub feed_end_date: Option<NaiveDate>,
+    pub feed_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CalendarDate {
+    pub service_id: String,
+    #[serde(with = "gtfs_date")]
+    pub date: NaiveDate,
+    pub exception_type: u8,
+}
+
+pub struct ServiceDay<'a> {
+    services: HashSet<&'a str>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Departure {
+    pub trip_id: String,
+    pub stop_sequence: u32,
+    pub time: GtfsTime,
+    pub headway: Option<u32>,
+    pub accessible: bool,
+    pub route_short_name: String,
+    pub headsign: String,
+}
+
+impl ServiceDay<'_> {
+    pub fn runs(&self, trip: &Trip) -> bool {
+        self.services.contains(trip.service_id.as_str())
+    }
 }
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct TransitData {
+    pub agencies: Vec<Agency>,
     pub stops: Vec<Stop>,
     pub trips: Vec<Trip>,
     pub routes: Vec<Route>,
     pub stop_times: Vec<StopTime>,
+    pub calendars: Vec<Calendar>,
+    pub calendar_dates: Vec<CalendarDate>,
+    pub shapes: Vec<Shape>,
+    pub frequencies: Vec<Frequency>,
+    pub transfers: Vec<Transfer>,
+    pub fare_attributes: Vec<FareAttribute>,
+    pub fare_rules: Vec<FareRule>,
+    pub fare_products: Vec<FareProduct>,
+    pub fare_leg_rules: Vec<FareLegRule>,
+    pub areas: Vec<Area>,
+    pub stop_areas: Vec<StopArea>,
+    pub pathways: Vec<Pathway>,
+    pub levels: Vec<Level>,
+    pub feeds: Vec<LoadedFeed>,
+    pub versions: HashMap<String, FeedVersion>,
+    pub errors: Vec<GtfsError>,
+    #[serde(skip)]
+    stop_index: HashMap<String, usize>,
+    #[serde(skip)]
+    trip_index: HashMap<String, usize>,
+    #[serde(skip)]
+    route_index: HashMap<String, usize>,
+    #[serde(skip)]
+    child_stops: HashMap<String, Vec<usize>>,
+    #[serde(skip)]
+    trip_stop_times: HashMap<String, Vec<usize>>,
+    #[serde(skip)]
+    stop_times_at: HashMap<String, Vec<usize>>,
+    #[serde(skip)]
+    trip_instances: HashMap<String, Vec<TripInstance>>,
+    #[serde(skip)]
+    station_links: HashMap<String, String>,
+    #[serde(skip)]
+    pathways_from: HashMap<String, Vec<usize>>,
+    #[serde(skip)]
+    search_index: StopIndex,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoadedFeed {
+    pub source: String,
+    pub records: FeedRecords,
+    pub info: Option<FeedInfo>,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_until: Option<NaiveDate>,
+}
+
+// Where the records of a feed are in the tables of `TransitData`, so that feeds
+// can be taken over from the cache on their own.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct FeedRecords {
+    pub agencies: Range<usize>,
+    pub stops: Range<usize>,
+    pub trips: Range<usize>,
+    pub routes: Range<usize>,
+    pub stop_times: Range<usize>,
+    pub calendars: Range<usize>,
+    pub calendar_dates: Range<usize>,
+    pub shapes: Range<usize>,
+    pub frequencies: Range<usize>,
+    pub transfers: Range<usize>,
+    pub fare_attributes: Range<usize>,
+    pub fare_rules: Range<usize>,
+    pub fare_products: Range<usize>,
+    pub fare_leg_rules: Range<usize>,
+    pub areas: Range<usize>,
+    pub stop_areas: Range<usize>,
+    pub pathways: Range<usize>,
+    pub levels: Range<usize>,
+}
+
+impl LoadedFeed {
+    pub fn is_valid_on(&self, date: NaiveDate) -> bool {
+        self.valid_from.is_none_or(|from| from <= date)
+            && self.valid_until.is_none_or(|until| date <= until)
+    }
+
+    pub fn name(&self) -> String {
+        match &self.info {
+            Some(info) => match &info.feed_version {
+                Some(version) => format!("{} {version}", info.feed_publisher_name),
+                None => info.feed_publisher_name.clone(),
+            },
+            None => self.source.clone(),
+        }
+    }
 }
 
 impl TransitData {
     #[tracing::instrument]
-    pub async fn load() -> Self {
-        let resp = reqwest::get("https://projekte.kvv-efa.de/GTFS/google_transit.zip")
-            .await
-            .unwrap()
-            .bytes()
-            .await
-            .unwrap();
+    pub async fn load(sources: &[FeedConfig]) -> Self {
+        let mut transit_data = Self::default();
+
+        for source in sources {
+            let feed = Feed::open(&source.source, None).await;
+            transit_data.add_feed(source, feed);
+        }
+
+        transit_data.finish()
+    }
+
+    // Rebuilds the transit data if any feed changed since `cached` was loaded. Only
+    // changed feeds are read again, the others are taken over from the cache. Feeds
+    // that cannot be reached count as unchanged, so the cache keeps working offline.
+    #[tracing::instrument(skip(cached))]
+    pub async fn load_if_changed(sources: &[FeedConfig], cached: &TransitData) -> Option<Self> {
+        let mut changed = sources.len() != cached.versions.len();
+        let mut feeds = Vec::new();
+
+        for source in sources {
+            let previous = cached.versions.get(&source.to_string());
+            let feed = Feed::open(&source.source, previous).await;
+            match &feed {
+                Ok(Some(_)) => changed = true,
+                Ok(None) => {}
+                Err(err) => {
+                    warn!("{err}");
+                    changed |= previous.is_none();
+                }
+            }
+            feeds.push(feed);
+        }
+
+        if !changed {
+            return None;
+        }
+
+        let mut transit_data = Self::default();
+        for (source, feed) in sources.iter().zip(feeds) {
+            let unchanged = matches!(feed, Ok(None) | Err(_));
+            if !unchanged || !transit_data.add_cached_feed(source, cached) {
+                transit_data.add_feed(source, feed);
+            }
+        }
+
+        Some(transit_data.finish())
+    }
+
+    fn add_feed(
+        &mut self,
+        source: &FeedConfig,
+        feed: Result<Option<(Feed, FeedVersion)>, GtfsError>,
+    ) {
+        let (mut feed, version) = match feed {
+            Ok(Some(feed)) => feed,
+            Ok(None) => return,
+            Err(err) => {
+                self.errors.push(err);
+                return;
+            }
+        };
+
+        self.versions.insert(source.to_string(), version);
+
+        let ns = source.namespace.as_deref();
+        let errors = &mut self.errors;
+        let agencies = extend(&mut self.agencies, load_agencies(&mut feed, errors), ns);
+        let stops = extend(&mut self.stops, load_stops(&mut feed, errors), ns);
+        let trips = extend(&mut self.trips, load_trips(&mut feed, errors), ns);
+        let routes = extend(&mut self.routes, load_routes(&mut feed, errors), ns);
+        let stop_times = extend(&mut self.stop_times, load_stop_times(&mut feed, errors), ns);
+        let calendars = load_calendars(&mut feed, errors);
+        let calendar_dates = load_calendar_dates(&mut feed, errors);
+        let info = load_feed_info(&mut feed, errors);
+
+        // Without feed_info.txt the feed is valid for as long as any of its services run.
+        let service_dates = calendars
+            .iter()
+            .flat_map(|c| [c.start_date, c.end_date])
+            .chain(
+                calendar_dates
+                    .iter()
+                    .filter(|d| d.exception_type == SERVICE_ADDED)
+                    .map(|d| d.date),
+            );
+        let valid_from = info
+            .as_ref()
+            .and_then(|i| i.feed_start_date)
+            .or_else(|| service_dates.clone().min());
+        let valid_until = info
+            .as_ref()
+            .and_then(|i| i.feed_end_date)
+            .or_else(|| service_dates.max());
+
+        let records = FeedRecords {
+            agencies,
+            stops,
+            trips,
+            routes,
+            stop_times,
+            calendars: extend(&mut self.calendars, calendars, ns),
+            calendar_dates: extend(&mut self.calendar_dates, calendar_dates, ns),
+            shapes: extend(&mut self.shapes, load_shapes(&mut feed, errors), ns),
+            frequencies: extend(
+                &mut self.frequencies,
+                load_frequencies(&mut feed, errors),
+                ns,
+            ),
+            transfers: extend(&mut self.transfers, load_transfers(&mut feed, errors), ns),
+            fare_attributes: extend(
+                &mut self.fare_attributes,
+                load_fare_attributes(&mut feed, errors),
+                ns,
+            ),
+            fare_rules: extend(&mut self.fare_rules, load_fare_rules(&mut feed, errors), ns),
+            fare_products: extend(
+                &mut self.fare_products,
+                load_fare_products(&mut feed, errors),
+                ns,
+            ),
+            fare_leg_rules: extend(
+                &mut self.fare_leg_rules,
+                load_fare_leg_rules(&mut feed, errors),
+                ns,
+            ),
+            areas: extend(&mut self.areas, load_areas(&mut feed, errors), ns),
+            stop_areas: extend(&mut self.stop_areas, load_stop_areas(&mut feed, errors), ns),
+            pathways: extend(&mut self.pathways, load_pathways(&mut feed, errors), ns),
+            levels: extend(&mut self.levels, load_levels(&mut feed, errors), ns),
+        };
+
+        self.feeds.push(LoadedFeed {
+            source: source.to_string(),
+            records,
+            info,
+            valid_from,
+            valid_until,
+        });
+    }
+
+    // Copies the records of a feed from `cached`. Returns false if the feed was
+    // not loaded before.
+    fn add_cached_feed(&mut self, source: &FeedConfig, cached: &TransitData) -> bool {
+        let source = source.to_string();
+        let Some(feed) = cached.feeds.iter().find(|f| f.source == source) else {
+            return false;
+        };
+
+        let cached_records = &feed.records;
+        let records = FeedRecords {
+            agencies: copy(
+                &mut self.agencies,
+                &cached.agencies,
+                &cached_records.agencies,
+            ),
+            stops: copy(&mut self.stops, &cached.stops, &cached_records.stops),
+            trips: copy(&mut self.trips, &cached.trips, &cached_records.trips),
+            routes: copy(&mut self.routes, &cached.routes, &cached_records.routes),
+            stop_times: copy(
+                &mut self.stop_times,
+                &cached.stop_times,
+                &cached_records.stop_times,
+            ),
+            calendars: copy(
+                &mut self.calendars,
+                &cached.calendars,
+                &cached_records.calendars,
+            ),
+            calendar_dates: copy(
+                &mut self.calendar_dates,
+                &cached.calendar_dates,
+                &cached_records.calendar_dates,
+            ),
+            shapes: copy(&mut self.shapes, &cached.shapes, &cached_records.shapes),
+            frequencies: copy(
+                &mut self.frequencies,
+                &cached.frequencies,
+                &cached_records.frequencies,
+            ),
+            transfers: copy(
+                &mut self.transfers,
+                &cached.transfers,
+                &cached_records.transfers,
+            ),
+            fare_attributes: copy(
+                &mut self.fare_attributes,
+                &cached.fare_attributes,
+                &cached_records.fare_attributes,
+            ),
+            fare_rules: copy(
+                &mut self.fare_rules,
+                &cached.fare_rules,
+                &cached_records.fare_rules,
+            ),
+            fare_products: copy(
+                &mut self.fare_products,
+                &cached.fare_products,
+                &cached_records.fare_products,
+            ),
+            fare_leg_rules: copy(
+                &mut self.fare_leg_rules,
+                &cached.fare_leg_rules,
+                &cached_records.fare_leg_rules,
+            ),
+            areas: copy(&mut self.areas, &cached.areas, &cached_records.areas),
+            stop_areas: copy(
+                &mut self.stop_areas,
+                &cached.stop_areas,
+                &cached_records.stop_areas,
+            ),
+            pathways: copy(
+                &mut self.pathways,
+                &cached.pathways,
+                &cached_records.pathways,
+            ),
+            levels: copy(&mut self.levels, &cached.levels, &cached_records.levels),
+        };
+
+        if let Some(version) = cached.versions.get(&source) {
+            self.versions.insert(source, version.clone());
+        }
+        self.feeds.push(LoadedFeed {
+            records,
+            ..feed.clone()
+        });
+
+        true
+    }
+
+    // The records of one of the loaded feeds on their own, e.g. to compare them
+    // with another version of the feed.
+    pub fn feed(&self, source: &FeedConfig) -> Option<Self> {
+        let mut transit_data = Self::default();
+        if !transit_data.add_cached_feed(source, self) {
+            return None;
+        }
+
+        transit_data.build_index();
+        Some(transit_data)
+    }
+
+    fn finish(mut self) -> Self {
+        for err in &self.errors {
+            warn!("{err}");
+        }
+
+        self.build_index();
+        self
+    }
+
+    pub fn build_index(&mut self) {
+        self.stop_index = self
+            .stops
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.stop_id.clone(), i))
+            .collect();
+        self.trip_index = self
+            .trips
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.trip_id.clone(), i))
+            .collect();
+        self.route_index = self
+            .routes
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (r.route_id.clone(), i))
+            .collect();
+
+        self.trip_stop_times.clear();
+        self.stop_times_at.clear();
+        for (i, stop_time) in self.stop_times.iter().enumerate() {
+            self.trip_stop_times
+                .entry(stop_time.trip_id.clone())
+                .or_default()
+                .push(i);
+            self.stop_times_at
+                .entry(stop_time.stop_id.clone())
+                .or_default()
+                .push(i);
+        }
+        for stop_times in self.trip_stop_times.values_mut() {
+            stop_times.sort_by_key(|i| self.stop_times[*i].stop_sequence);
+        }
+
+        self.child_stops.clear();
+        for (i, stop) in self.stops.iter().enumerate() {
+            if let Some(parent) = &stop.parent_station {
+                self.child_stops.entry(parent.clone()).or_default().push(i);
+            }
+        }
+
+        self.pathways_from.clear();
+        for (i, pathway) in self.pathways.iter().enumerate() {
+            self.pathways_from
+                .entry(pathway.from_stop_id.clone())
+                .or_default()
+                .push(i);
+            if pathway.is_bidirectional == 1 {
+                self.pathways_from
+                    .entry(pathway.to_stop_id.clone())
+                    .or_default()
+                    .push(i);
+            }
+        }
+
+        self.link_stations();
+        self.index_frequencies();
+
+        // Linked stations are found through the station they are linked to.
+        let links = &self.station_links;
+        self.search_index = StopIndex::new(&self.stops, |s| {
+            (s.is_station() || s.is_platform())
+                && s.parent_station.is_none()
+                && !links.contains_key(&s.stop_id)
+        });
+    }
+
+    // Links stations of later feeds to a nearby station with a similar name in
+    // an earlier feed. Linked stations resolve to the earlier station and are
+    // listed as its children.
+    fn link_stations(&mut self) {
+        self.station_links.clear();
+        if self.feeds.len() < 2 {
+            return;
+        }
+
+        let stations: Vec<usize> = (0..self.stops.len())
+            .filter(|i| {
+                let stop = &self.stops[*i];
+                stop.is_station() || stop.parent_station.is_none()
+            })
+            .collect();
+        let feed_of = |i: usize| self.feeds.iter().position(|f| f.records.stops.contains(&i));
+
+        let stops: Vec<&Stop> = stations.iter().map(|i| &self.stops[*i]).collect();
+        let mut links: HashMap<usize, (usize, f64)> = HashMap::new();
+        for (a, b, distance) in nearby_stops(&stops, STATION_LINK_DISTANCE) {
+            let (a, b) = (stations[a], stations[b]);
+            if feed_of(a) >= feed_of(b)
+                || !similar_names(self.stops[a].name(), self.stops[b].name())
+            {
+                continue;
+            }
 
-        let mut zip = ZipArchive::new(Cursor::new(resp)).unwrap();
+            let link = links.entry(b).or_insert((a, distance));
+            if distance < link.1 {
+                *link = (a, distance);
+            }
+        }
+
+        for (&linked, &(station, _)) in &links {
+            let mut station = station;
+            while let Some((next, _)) = links.get(&station) {
+                station = *next;
+            }
+
+            self.station_links.insert(
+                self.stops[linked].stop_id.clone(),
+                self.stops[station].stop_id.clone(),
+            );
+            self.child_stops
+                .entry(self.stops[station].stop_id.clone())
+                .or_default()
+                .push(linked);
+        }
+    }
+
+    fn index_frequencies(&mut self) {
+        self.trip_instances.clear();
+        if self.frequencies.is_empty() {
+            return;
+        }
+
+        for frequency in &self.frequencies {
+            let Some(first_departure) = self
+                .trip_stop_times(&frequency.trip_id)
+                .first()
+                .and_then(|st| st.departure_time.or(st.arrival_time))
+            else {
+                continue;
+            };
+
+            // Frequencies without a departure leave the trip as scheduled.
+            if frequency.start_time >= frequency.end_time || frequency.headway_secs == 0 {
+                continue;
+            }
+
+            let instances = self
+                .trip_instances
+                .entry(frequency.trip_id.clone())
+                .or_default();
+            let mut start = frequency.start_time.0;
+            while start < frequency.end_time.0 {
+                instances.push(TripInstance {
+                    shift: start as i64 - first_departure.0 as i64,
+                    headway: Some(frequency.headway_secs),
+                });
+                start += frequency.headway_secs;
+            }
+        }
+    }
+
+    pub fn trip_instances(&self, trip_id: &str) -> &[TripInstance] {
+        self.trip_instances
+            .get(trip_id)
+            .map(|i| i.as_slice())
+            .unwrap_or(SINGLE_INSTANCE)
+    }
+
+    pub fn stop(&self, stop_id: &str) -> Option<&Stop> {
+        self.stop_index.get(stop_id).map(|i| &self.stops[*i])
+    }
+
+    pub fn trip(&self, trip_id: &str) -> Option<&Trip> {
+        self.trip_index.get(trip_id).map(|i| &self.trips[*i])
+    }
+
+    pub fn route(&self, route_id: &str) -> Option<&Route> {
+        self.route_index.get(route_id).map(|i| &self.routes[*i])
+    }
+
+    pub fn search_stops(&self, query: &str, limit: usize) -> Vec<&Stop> {
+        self.search_index
+            .search(query, limit)
+            .into_iter()
+            .map(|i| &self.stops[i])
+            .collect()
+    }
+
+    pub fn nearest_stops(&self, lat: f64, lon: f64, count: usize) -> Vec<(&Stop, f64)> {
+        self.search_index
+            .nearest(&self.stops, lat, lon, count)
+            .into_iter()
+            .map(|(i, distance)| (&self.stops[i], distance))
+            .collect()
+    }
+
+    // Resolves platforms, entrances and boarding areas to the station they belong to.
+    // Stops without a parent station are their own station.
+    pub fn station(&self, stop_id: &str) -> Option<&Stop> {
+        let mut stop = self.stop(stop_id)?;
+        for _ in 0..MAX_PARENT_DEPTH {
+            if stop.is_station() {
+                break;
+            }
+            match stop.parent_station.as_deref().and_then(|p| self.stop(p)) {
+                Some(parent) => stop = parent,
+                None => break,
+            }
+        }
+
+        match self.station_links.get(&stop.stop_id) {
+            Some(linked) => self.stop(linked),
+            None => Some(stop),
+        }
+    }
+
+    // The stop itself and all of its descendants.
+    pub fn stop_ids_at<'a>(&'a self, stop_id: &'a str) -> HashSet<&'a str> {
+        let mut stop_ids = HashSet::from([stop_id]);
+        let mut pending = vec![stop_id];
+        while let Some(stop_id) = pending.pop() {
+            for child in self.child_stops.get(stop_id).into_iter().flatten() {
+                let child = self.stops[*child].stop_id.as_str();
+                if stop_ids.insert(child) {
+                    pending.push(child);
+                }
+            }
+        }
+
+        stop_ids
+    }
+
+    // Stations with pathways are step-free if every platform can be reached from
+    // an entrance without stairs or escalators. Otherwise wheelchair_boarding of the
+    // platforms is used, where platforms without a value inherit it from the station.
+    pub fn accessibility(&self, station_id: &str) -> Accessibility {
+        let Some(station) = self.stop(station_id) else {
+            return Accessibility::Unknown;
+        };
+
+        let stop_ids = self.stop_ids_at(station_id);
+        let stops: Vec<&Stop> = stop_ids.iter().filter_map(|id| self.stop(id)).collect();
+        let platforms: Vec<&Stop> = stops
+            .iter()
+            .filter(|s| s.is_platform() && s.stop_id != station_id)
+            .copied()
+            .collect();
+
+        let entrances: Vec<&str> = stops
+            .iter()
+            .filter(|s| s.location_type == LOCATION_TYPE_ENTRANCE)
+            .map(|s| s.stop_id.as_str())
+            .collect();
+        if !entrances.is_empty()
+            && stop_ids
+                .iter()
+                .any(|id| self.pathways_from.contains_key(*id))
+        {
+            let reachable = self.step_free_reachable(entrances);
+            return if platforms
+                .iter()
+                .all(|p| reachable.contains(p.stop_id.as_str()))
+            {
+                Accessibility::StepFree
+            } else {
+                Accessibility::NotAccessible
+            };
+        }
+
+        let boarding: Vec<u8> = if platforms.is_empty() {
+            vec![station.wheelchair_boarding]
+        } else {
+            platforms
+                .iter()
+                .map(|p| match p.wheelchair_boarding {
+                    0 => station.wheelchair_boarding,
+                    value => value,
+                })
+                .collect()
+        };
+
+        if boarding.iter().all(|b| *b == WHEELCHAIR_ACCESSIBLE) {
+            Accessibility::StepFree
+        } else if boarding.contains(&WHEELCHAIR_INACCESSIBLE) {
+            Accessibility::NotAccessible
+        } else {
+            Accessibility::Unknown
+        }
+    }
+
+    fn step_free_reachable<'a>(&'a self, entrances: Vec<&'a str>) -> HashSet<&'a str> {
+        let mut reachable: HashSet<&str> = entrances.iter().copied().collect();
+        let mut pending = entrances;
+        while let Some(stop_id) = pending.pop() {
+            for pathway in self
+                .pathways_from
+                .get(stop_id)
+                .into_iter()
+                .flatten()
+                .map(|i| &self.pathways[*i])
+                .filter(|p| p.is_step_free())
+            {
+                let next = if pathway.from_stop_id == stop_id {
+                    pathway.to_stop_id.as_str()
+                } else {
+                    pathway.from_stop_id.as_str()
+                };
+                if reachable.insert(next) {
+                    pending.push(next);
+                }
+            }
+        }
+
+        reachable
+    }
+
+    // Levels of a station, from the lowest to the highest.
+    pub fn levels_at(&self, station_id: &str) -> Vec<&Level> {
+        let level_ids: HashSet<&str> = self
+            .stop_ids_at(station_id)
+            .into_iter()
+            .filter_map(|id| self.stop(id)?.level_id.as_deref())
+            .collect();
+
+        let mut levels: Vec<&Level> = self
+            .levels
+            .iter()
+            .filter(|l| level_ids.contains(l.level_id.as_str()))
+            .collect();
+        levels.sort_by(|a, b| a.level_index.total_cmp(&b.level_index));
+        levels
+    }
+
+    pub fn service_day(&self, date: NaiveDate) -> ServiceDay<'_> {
+        let mut services: HashSet<&str> = self
+            .calendars
+            .iter()
+            .filter(|c| c.runs_on(date))
+            .map(|c| c.service_id.as_str())
+            .collect();
+
+        for calendar_date in self.calendar_dates.iter().filter(|cd| cd.date == date) {
+            match calendar_date.exception_type {
+                SERVICE_ADDED => {
+                    services.insert(&calendar_date.service_id);
+                }
+                SERVICE_REMOVED => {
+                    services.remove(calendar_date.service_id.as_str());
+                }
+                _ => {}
+            }
+        }
+
+        ServiceDay { services }
+    }
+
+    pub fn shapes_by_id(&self) -> HashMap<&str, Vec<&Shape>> {
+        let mut shapes: HashMap<&str, Vec<&Shape>> = HashMap::new();
+        for shape in &self.shapes {
+            shapes.entry(&shape.shape_id).or_default().push(shape);
+        }
+
+        for points in shapes.values_mut() {
+            points.sort_by_key(|s| s.shape_pt_sequence);
+        }
+
+        shapes
+    }
+
+    // Stop times of a trip in stop sequence.
+    pub fn trip_stop_times(&self, trip_id: &str) -> Vec<&StopTime> {
+        self.trip_stop_times
+            .get(trip_id)
+            .into_iter()
+            .flatten()
+            .map(|i| &self.stop_times[*i])
+            .collect()
+    }
+
+    pub fn departures(
+        &self,
+        station_id: &str,
+        date: NaiveDate,
+        after: GtfsTime,
+        count: usize,
+        accessible_only: bool,
+    ) -> Vec<Departure> {
+        // Trips of the previous service day can still be running after midnight,
+        // their times are shifted back by a full day.
+        let service_days = [
+            (self.service_day(date), 0),
+            (self.service_day(date - Days::new(1)), SECONDS_PER_DAY),
+        ];
+
+        let mut departures: Vec<(GtfsTime, Option<u32>, &StopTime, &Trip, &Route)> = Vec::new();
+        let stop_times = self
+            .stop_ids_at(station_id)
+            .into_iter()
+            .filter_map(|id| self.stop_times_at.get(id))
+            .flatten()
+            .map(|i| &self.stop_times[*i]);
+        for stop_time in stop_times {
+            let Some(departure_time) = stop_time.departure_time.or(stop_time.arrival_time) else {
+                continue;
+            };
+
+            let Some(trip) = self
+                .trip(&stop_time.trip_id)
+                .filter(|t| !accessible_only || t.is_accessible())
+            else {
+                continue;
+            };
 
-        Self {
-            stops: load_stops(&mut zip),
-            trips: load_trips(&mut zip),
-            routes: load_routes(&mut zip),
-            stop_times: load_stop_times(&mut zip),
+            let Some(route) = self.route(&trip.route_id) else {
+                continue;
+            };
+
+            for instance in self.trip_instances(&trip.trip_id) {
+                let Some(departure_time) = instance.apply(departure_time) else {
+                    continue;
+                };
+
+                for (service_day, shift) in &service_days {
+                    if departure_time.0 < *shift || !service_day.runs(trip) {
+                        continue;
+                    }
+
+                    let time = GtfsTime(departure_time.0 - shift);
+                    if time >= after {
+                        departures.push((time, instance.headway, stop_time, trip, route));
+                    }
+                }
+            }
         }
+
+        departures.sort_by(|(a, _, a_stop_time, _, _), (b, _, b_stop_time, _, _)| {
+            a.cmp(b).then(a_stop_time.trip_id.cmp(&b_stop_time.trip_id))
+        });
+        // Frequency based trips are listed once, at their next departure.
+        let mut listed = HashSet::new();
+        departures.retain(|(_, headway, _, trip, _)| {
+            headway.is_none() || listed.insert((trip.trip_id.as_str(), *headway))
+        });
+        departures.truncate(count);
+
+        let last_stops =
+            self.last_stop_names(departures.iter().map(|(_, _, _, t, _)| t.trip_id.as_str()));
+
+        departures
+            .into_iter()
+            .map(|(time, headway, stop_time, trip, route)| Departure {
+                trip_id: trip.trip_id.clone(),
+                stop_sequence: stop_time.stop_sequence,
+                time,
+                headway,
+                accessible: trip.is_accessible(),
+                route_short_name: route.route_short_name.clone(),
+                headsign: trip
+                    .trip_headsign
+                    .clone()
+                    .or_else(|| last_stops.get(trip.trip_id.as_str()).cloned())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    pub fn last_stop_names<'a>(
+        &'a self,
+        trip_ids: impl Iterator<Item = &'a str>,
+    ) -> HashMap<&'a str, String> {
+        trip_ids
+            .filter_map(|trip_id| {
+                let last = self.trip_stop_times.get(trip_id)?.last()?;
+                let station = self.station(&self.stop_times[*last].stop_id)?;
+                Some((trip_id, station.name().to_string()))
+            })
+            .collect()
     }
 }
 
-fn load_stops(zip: &mut ZipArchive<Cursor<Bytes>>) -> Vec<Stop> {
-    let stops_file = zip.by_name("stops.txt").unwrap();
+fn load_agencies(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Agency> {
+    read_file(feed, "agency.txt", errors)
+}
+
+fn load_stops(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Stop> {
+    read_file(feed, "stops.txt", errors)
+}
+
+fn load_trips(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Trip> {
+    read_file(feed, "trips.txt", errors)
+}
+
+fn load_routes(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Route> {
+    read_file(feed, "routes.txt", errors)
+}
 
-    let mut csv_reader = csv::Reader::from_reader(stops_file);
-    let mut stops: Vec<Stop> = csv_reader.deserialize().map(|r| r.unwrap()).collect();
-    stops.sort_by_key(|s| s.stop_name.clone());
-    stops.dedup_by_key(|s| s.stop_name.clone());
-    stops
+fn load_stop_times(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<StopTime> {
+    read_file(feed, "stop_times.txt", errors)
 }
 
-fn load_trips(zip: &mut ZipArchive<Cursor<Bytes>>) -> Vec<Trip> {
-    let trips_file = zip.by_name("trips.txt").unwrap();
+fn load_calendars(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Calendar> {
+    read_optional_file(feed, "calendar.txt", errors)
+}
 
-    let mut csv_reader = csv::Reader::from_reader(trips_file);
-    let trips: Vec<Trip> = csv_reader.deserialize().map(|r| r.unwrap()).collect();
-    trips
+fn load_calendar_dates(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<CalendarDate> {
+    read_optional_file(feed, "calendar_dates.txt", errors)
 }
 
-fn load_routes(zip: &mut ZipArchive<Cursor<Bytes>>) -> Vec<Route> {
-    let routes_file = zip.by_name("routes.txt").unwrap();
+fn load_shapes(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Shape> {
+    read_optional_file(feed, "shapes.txt", errors)
+}
 
-    let mut csv_reader = csv::Reader::from_reader(routes_file);
-    let mut routes: Vec<Route> = csv_reader.deserialize().map(|r| r.unwrap()).collect();
-    routes.sort_by_key(|r| r.route_short_name.clone());
-    routes.dedup_by_key(|r| r.route_short_name.clone());
-    routes
+fn load_feed_info(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Option<FeedInfo> {
+    read_optional_file(feed, "feed_info.txt", errors)
         .into_iter()
-        .filter(|r| {
-            (r.agency_id == VBK_AGENCY_ID || r.agency_id == ALBTAL_AGENCY_ID) && r.route_type < 3
-        })
-        .collect()
+        .next()
+}
+
+fn load_frequencies(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Frequency> {
+    read_optional_file(feed, "frequencies.txt", errors)
+}
+
+fn load_transfers(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Transfer> {
+    read_optional_file(feed, "transfers.txt", errors)
+}
+
+fn load_fare_attributes(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<FareAttribute> {
+    read_optional_file(feed, "fare_attributes.txt", errors)
+}
+
+fn load_fare_rules(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<FareRule> {
+    read_optional_file(feed, "fare_rules.txt", errors)
+}
+
+fn load_fare_products(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<FareProduct> {
+    read_optional_file(feed, "fare_products.txt", errors)
+}
+
+fn load_fare_leg_rules(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<FareLegRule> {
+    read_optional_file(feed, "fare_leg_rules.txt", errors)
+}
+
+fn load_areas(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Area> {
+    read_optional_file(feed, "areas.txt", errors)
+}
+
+fn load_stop_areas(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<StopArea> {
+    read_optional_file(feed, "stop_areas.txt", errors)
+}
+
+fn load_pathways(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Pathway> {
+    read_optional_file(feed, "pathways.txt", errors)
+}
+
+fn load_levels(feed: &mut Feed, errors: &mut Vec<GtfsError>) -> Vec<Level> {
+    read_optional_file(feed, "levels.txt", errors)
+}
+
+// Prefixes all IDs of a record with the namespace of its feed.
+trait Namespaced {
+    fn namespace(&mut self, namespace: &str);
+}
+
+fn prefix(namespace: &str, id: &mut String) {
+    *id = format!("{namespace}:{id}");
+}
+
+fn prefix_optional(namespace: &str, id: &mut Option<String>) {
+    if let Some(id) = id {
+        prefix(namespace, id);
+    }
+}
+
+// Appends the records of a feed and returns where they are in `target`.
+fn extend<T: Namespaced>(
+    target: &mut Vec<T>,
+    mut records: Vec<T>,
+    namespace: Option<&str>,
+) -> Range<usize> {
+    if let Some(namespace) = namespace {
+        for record in &mut records {
+            record.namespace(namespace);
+        }
+    }
+
+    let start = target.len();
+    target.extend(records);
+    start..target.len()
+}
+
+fn copy<T: Clone>(target: &mut Vec<T>, source: &[T], range: &Range<usize>) -> Range<usize> {
+    let start = target.len();
+    target.extend_from_slice(&source[range.clone()]);
+    start..target.len()
+}
+
+impl Namespaced for Agency {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.agency_id);
+    }
+}
+
+impl Namespaced for Stop {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.stop_id);
+        prefix_optional(namespace, &mut self.parent_station);
+        prefix_optional(namespace, &mut self.zone_id);
+        prefix_optional(namespace, &mut self.level_id);
+    }
+}
+
+impl Namespaced for Trip {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.trip_id);
+        prefix(namespace, &mut self.route_id);
+        prefix(namespace, &mut self.service_id);
+        prefix_optional(namespace, &mut self.shape_id);
+    }
+}
+
+impl Namespaced for Route {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.route_id);
+        prefix(namespace, &mut self.agency_id);
+        prefix_optional(namespace, &mut self.network_id);
+    }
+}
+
+impl Namespaced for StopTime {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.trip_id);
+        prefix(namespace, &mut self.stop_id);
+    }
+}
+
+impl Namespaced for Calendar {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.service_id);
+    }
+}
+
+impl Namespaced for CalendarDate {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.service_id);
+    }
+}
+
+impl Namespaced for Shape {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.shape_id);
+    }
+}
+
+impl Namespaced for Frequency {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.trip_id);
+    }
+}
+
+impl Namespaced for Transfer {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.from_stop_id);
+        prefix(namespace, &mut self.to_stop_id);
+    }
+}
+
+impl Namespaced for FareAttribute {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.fare_id);
+        prefix_optional(namespace, &mut self.agency_id);
+    }
+}
+
+impl Namespaced for FareRule {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.fare_id);
+        prefix_optional(namespace, &mut self.route_id);
+        prefix_optional(namespace, &mut self.origin_id);
+        prefix_optional(namespace, &mut self.destination_id);
+    }
+}
+
+impl Namespaced for FareProduct {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.fare_product_id);
+    }
+}
+
+impl Namespaced for FareLegRule {
+    fn namespace(&mut self, namespace: &str) {
+        prefix_optional(namespace, &mut self.network_id);
+        prefix_optional(namespace, &mut self.from_area_id);
+        prefix_optional(namespace, &mut self.to_area_id);
+        prefix(namespace, &mut self.fare_product_id);
+    }
+}
+
+impl Namespaced for Area {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.area_id);
+    }
+}
+
+impl Namespaced for StopArea {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.area_id);
+        prefix(namespace, &mut self.stop_id);
+    }
+}
+
+impl Namespaced for Pathway {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.pathway_id);
+        prefix(namespace, &mut self.from_stop_id);
+        prefix(namespace, &mut self.to_stop_id);
+    }
+}
+
+impl Namespaced for Level {
+    fn namespace(&mut self, namespace: &str) {
+        prefix(namespace, &mut self.level_id);
+    }
+}
+
+fn read_file<T: DeserializeOwned>(
+    feed: &mut Feed,
+    file: &str,
+    errors: &mut Vec<GtfsError>,
+) -> Vec<T> {
+    match feed.file(file) {
+        Ok(Some(reader)) => read_records(reader, file, errors),
+        Ok(None) => {
+            errors.push(GtfsError::MissingFile {
+                file: file.to_string(),
+            });
+            Vec::new()
+        }
+        Err(err) => {
+            errors.push(err);
+            Vec::new()
+        }
+    }
+}
+
+fn read_optional_file<T: DeserializeOwned>(
+    feed: &mut Feed,
+    file: &str,
+    errors: &mut Vec<GtfsError>,
+) -> Vec<T> {
+    match feed.file(file) {
+        Ok(Some(reader)) => read_records(reader, file, errors),
+        Ok(None) => Vec::new(),
+        Err(err) => {
+            errors.push(err);
+            Vec::new()
+        }
+    }
+}
+
+fn read_records<T: DeserializeOwned>(
+    reader: impl Read,
+    file: &str,
+    errors: &mut Vec<GtfsError>,
+) -> Vec<T> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = match csv_reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => {
+            errors.push(GtfsError::row(file, &err, None));
+            return Vec::new();
+        }
+    };
+
+    let mut records = Vec::new();
+    for result in csv_reader.deserialize() {
+        match result {
+            Ok(record) => records.push(record),
+            Err(err) => errors.push(GtfsError::row(file, &err, Some(&headers))),
+        }
+    }
+
+    records
+}
+
+// Empty fields are read as the default value, like missing columns. Values are
+// written as options, so that they read back the same from the cache.
+mod empty_default {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, T: Serialize>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Some(value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de> + Default>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+    }
+}
+
+mod gtfs_date {
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y%m%d";
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&date.format(FORMAT).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let date = String::deserialize(deserializer)?;
+        NaiveDate::parse_from_str(&date, FORMAT).map_err(serde::de::Error::custom)
+    }
+
+    pub mod optional {
+        use chrono::NaiveDate;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            date: &Option<NaiveDate>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match date {
+                Some(date) => super::serialize(date, serializer),
+                None => serializer.serialize_str(""),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<NaiveDate>, D::Error> {
+            let date = String::deserialize(deserializer)?;
+            if date.trim().is_empty() {
+                return Ok(None);
+            }
+
+            NaiveDate::parse_from_str(date.trim(), super::FORMAT)
+                .map(Some)
+                .map_err(serde::de::Error::custom)
+        }
+    }
 }
 
-fn load_stop_times(zip: &mut ZipArchive<Cursor<Bytes>>) -> Vec<StopTime> {
-    let stop_times_file = zip.by_name("stop_times.txt").unwrap();
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn read<T: DeserializeOwned>(csv: &str) -> (Vec<T>, Vec<GtfsError>) {
+        let mut errors = Vec::new();
+        let records = read_records(csv.as_bytes(), "test.txt", &mut errors);
+        (records, errors)
+    }
+
+    fn write<T: Serialize>(records: &[T]) -> String {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for record in records {
+            writer.serialize(record).unwrap();
+        }
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    pub(crate) fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 19).unwrap()
+    }
+
+    // One route between two stops, with the trips running on `date()`.
+    pub(crate) fn small_feed(trips: &str, stop_times: &str) -> TransitData {
+        TransitData {
+            stops: read(
+                "stop_id,stop_name,stop_lat,stop_lon\n\
+                 a,A,49.0,8.4\n\
+                 b,B,49.01,8.4\n",
+            )
+            .0,
+            routes: read("route_id,route_short_name,route_type\nr,5,0\n").0,
+            trips: read(trips).0,
+            stop_times: read(stop_times).0,
+            calendar_dates: read("service_id,date,exception_type\nx,20261019,1\n").0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_location_type_is_a_platform() {
+        let (stops, errors): (Vec<Stop>, _) = read(
+            "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n\
+             s,Station,49.0,8.4,1,\n\
+             p,Platform,49.0,8.4,,s\n",
+        );
+
+        assert!(errors.is_empty());
+        assert!(stops[0].is_station());
+        assert!(stops[1].is_platform());
+    }
+
+    #[test]
+    fn generic_nodes_need_no_location() {
+        let (stops, errors): (Vec<Stop>, _) = read(
+            "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station,wheelchair_boarding\n\
+             s,Station,49.0,8.4,1,,1\n\
+             n,,,,3,s,\n",
+        );
+
+        assert!(errors.is_empty());
+        assert_eq!(stops[1].position(), None);
+        assert_eq!(stops[1].wheelchair_boarding, 0);
+        assert_eq!(stops[0].distance(&stops[1]), None);
+        assert!(nearby_stops(&[&stops[0], &stops[1]], 100.0).is_empty());
+    }
+
+    #[test]
+    fn empty_wheelchair_accessible_is_unknown() {
+        let (trips, errors): (Vec<Trip>, _) = read(
+            "trip_id,route_id,service_id,wheelchair_accessible\n\
+             t,r,weekday,\n",
+        );
+
+        assert!(errors.is_empty());
+        assert!(!trips[0].is_accessible());
+    }
+
+    #[test]
+    fn empty_transfer_type_is_recommended() {
+        let (transfers, errors): (Vec<Transfer>, _) = read(
+            "from_stop_id,to_stop_id,transfer_type,min_transfer_time\n\
+             a,b,,120\n",
+        );
+
+        assert!(errors.is_empty());
+        assert_eq!(transfers[0].transfer_type, 0);
+        assert_eq!(transfers[0].min_transfer_time, Some(120));
+    }
+
+    #[test]
+    fn unreachable_feeds_are_taken_from_the_cache() {
+        let copied = std::env::temp_dir().join(format!("licht-feed-{}", std::process::id()));
+        std::fs::create_dir_all(&copied).unwrap();
+        for entry in std::fs::read_dir("feeds/sample").unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), copied.join(entry.file_name())).unwrap();
+        }
+
+        let sources = [
+            FeedConfig {
+                source: FeedSource::Directory("feeds/sample".into()),
+                namespace: Some("a".to_string()),
+            },
+            FeedConfig {
+                source: FeedSource::Directory(copied.clone()),
+                namespace: Some("b".to_string()),
+            },
+        ];
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut cached = rt.block_on(TransitData::load(&sources));
+        std::fs::remove_dir_all(&copied).unwrap();
+
+        // The first feed changed and the second one is gone.
+        cached
+            .versions
+            .insert(sources[0].to_string(), FeedVersion::default());
+        let reloaded = rt
+            .block_on(TransitData::load_if_changed(&sources, &cached))
+            .unwrap();
+
+        assert_eq!(reloaded.feeds.len(), 2);
+        assert_eq!(reloaded.stops.len(), cached.stops.len());
+        assert_eq!(reloaded.stop_times.len(), cached.stop_times.len());
+        assert!(reloaded.stop("b:de:8212:1").is_some());
+        assert!(reloaded.versions.contains_key(&sources[1].to_string()));
+    }
+
+    #[test]
+    fn calendar_dates_add_and_remove_services() {
+        let td = TransitData {
+            trips: read(
+                "trip_id,route_id,service_id\n\
+                 weekday,r,w\n\
+                 special,r,s\n",
+            )
+            .0,
+            calendars: read(
+                "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n\
+                 w,1,1,1,1,1,0,0,20260101,20271231\n",
+            )
+            .0,
+            calendar_dates: read(
+                "service_id,date,exception_type\n\
+                 w,20261019,2\n\
+                 s,20261019,1\n",
+            )
+            .0,
+            ..Default::default()
+        };
+        let runs = |date: NaiveDate| -> Vec<&str> {
+            let service_day = td.service_day(date);
+            td.trips
+                .iter()
+                .filter(|t| service_day.runs(t))
+                .map(|t| t.trip_id.as_str())
+                .collect()
+        };
+
+        assert_eq!(runs(date()), ["special"]);
+        assert_eq!(runs(date().succ_opt().unwrap()), ["weekday"]);
+        assert!(runs(NaiveDate::from_ymd_opt(2026, 10, 24).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn departures_after_midnight_belong_to_the_previous_day() {
+        let mut td = small_feed(
+            "trip_id,route_id,service_id\nlate,r,x\n",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             late,24:50:00,24:50:00,a,1\n\
+             late,25:05:00,25:05:00,b,2\n",
+        );
+        td.build_index();
+        assert_eq!(td.stop_times[1].departure_time, Some(GtfsTime(90300)));
+        assert_eq!(
+            write(&td.stop_times[1..]).lines().nth(1),
+            Some("late,25:05:00,25:05:00,b,2")
+        );
+
+        let next_day = date().succ_opt().unwrap();
+        let departures = td.departures("a", next_day, GtfsTime::hms(0, 30, 0), 5, false);
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].time, GtfsTime::hms(0, 50, 0));
+        assert_eq!(departures[0].headsign, "B");
 
-    let mut csv_reader = csv::Reader::from_reader(stop_times_file);
-    let stop_times: Vec<StopTime> = csv_reader.deserialize().map(|r| r.unwrap()).collect();
-    stop_times
+        // On its own service day the trip departs at 24:50.
+        let departures = td.departures("a", date(), GtfsTime::hms(23, 0, 0), 5, false);
+        assert_eq!(departures[0].time, GtfsTime::hms(24, 50, 0));
+    }
+
+    #[test]
+    fn parent_station_cycles_end() {
+        let mut td = TransitData {
+            stops: read(
+                "stop_id,stop_name,stop_lat,stop_lon,parent_station\n\
+                 a,A,49.0,8.4,b\n\
+                 b,B,49.0,8.4,a\n",
+            )
+            .0,
+            ..Default::default()
+        };
+        td.build_index();
+
+        assert!(td.station("a").is_some());
+    }
+
+    #[test]
+    fn frequency_trips_are_listed_once() {
+        let mut td = small_feed(
+            "trip_id,route_id,service_id\nf,r,x\nt,r,x\n",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             f,06:00:00,06:00:00,a,1\n\
+             f,06:10:00,06:10:00,b,2\n\
+             t,06:20:00,06:20:00,a,1\n\
+             t,06:30:00,06:30:00,b,2\n",
+        );
+        td.frequencies =
+            read("trip_id,start_time,end_time,headway_secs\nf,06:00:00,07:00:00,300\n").0;
+        td.build_index();
+
+        let departures = td.departures("a", date(), GtfsTime::hms(6, 0, 0), 2, false);
+        let listed: Vec<(&str, Option<u32>)> = departures
+            .iter()
+            .map(|d| (d.trip_id.as_str(), d.headway))
+            .collect();
+        assert_eq!(listed, [("f", Some(300)), ("t", None)]);
+    }
+
+    #[test]
+    fn frequencies_without_departures_keep_the_schedule() {
+        let mut td = small_feed(
+            "trip_id,route_id,service_id\nf,r,x\n",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             f,06:00:00,06:00:00,a,1\n\
+             f,06:10:00,06:10:00,b,2\n",
+        );
+        td.frequencies = read(
+            "trip_id,start_time,end_time,headway_secs\n\
+             f,06:00:00,07:00:00,0\n\
+             f,08:00:00,08:00:00,300\n",
+        )
+        .0;
+        td.build_index();
+
+        let instances = td.trip_instances("f");
+        assert_eq!(instances.len(), 1);
+        assert_eq!((instances[0].shift, instances[0].headway), (0, None));
+    }
+
+    #[test]
+    fn fare_and_frequency_fields_are_written_back() {
+        let (fares, errors): (Vec<FareAttribute>, _) = read(
+            "fare_id,price,currency_type,payment_method,transfers,transfer_duration\n\
+             single,2.5,EUR,0,,5400\n",
+        );
+        assert!(errors.is_empty());
+        let (frequencies, errors): (Vec<Frequency>, _) = read(
+            "trip_id,start_time,end_time,headway_secs,exact_times\n\
+             f,06:00:00,07:00:00,300,1\n",
+        );
+        assert!(errors.is_empty());
+
+        assert_eq!(
+            write(&fares),
+            "fare_id,price,currency_type,payment_method,transfers,agency_id,transfer_duration\n\
+             single,2.5,EUR,0,,,5400\n"
+        );
+        assert_eq!(
+            write(&frequencies),
+            "trip_id,start_time,end_time,headway_secs,exact_times\n\
+             f,06:00:00,07:00:00,300,1\n"
+        );
+    }
+
+    #[test]
+    fn empty_fields_survive_the_cache() {
+        let (stops, _): (Vec<Stop>, _) = read(
+            "stop_id,stop_name,stop_lat,stop_lon,location_type\n\
+             p,Platform,49.0,8.4,\n",
+        );
+
+        let bytes = bincode::serialize(&stops).unwrap();
+        let cached: Vec<Stop> = bincode::deserialize(&bytes).unwrap();
+        assert!(cached[0].is_platform());
+    }
 }
@@ -0,0 +1,145 @@
+use chrono::NaiveDate;
+use egui::Color32;
+
+use crate::ui::{
+    gtfs::{GtfsTime, TransitData},
+    planner::{Planner, WALKING_SPEED},
+    state::Station,
+};
+
+// Upper limits of the bands in minutes, with their colour on the map.
+pub const BANDS: [(u32, Color32); 4] = [
+    (10, Color32::from_rgb(0x1a, 0x98, 0x50)),
+    (20, Color32::from_rgb(0xa6, 0xd9, 0x6a)),
+    (30, Color32::from_rgb(0xfd, 0xae, 0x61)),
+    (45, Color32::from_rgb(0xd7, 0x30, 0x27)),
+];
+
+#[derive(Clone)]
+pub struct Isochrone {
+    pub origin: Station,
+    pub departure: GtfsTime,
+    // Stations reached within the last band, with the travel time in seconds.
+    pub stations: Vec<(Station, u32)>,
+}
+
+impl Isochrone {
+    pub fn new(
+        td: &TransitData,
+        origin: Station,
+        date: NaiveDate,
+        departure: GtfsTime,
+        accessible_only: bool,
+    ) -> Self {
+        let planner = Planner::new(td, date, accessible_only);
+        let Some(from) = planner.station_index(&origin.stop_id) else {
+            return Self {
+                origin,
+                departure,
+                stations: Vec::new(),
+            };
+        };
+
+        let limit = BANDS[BANDS.len() - 1].0 * 60;
+        let scan = planner.scan(from, departure, None);
+        let mut stations: Vec<(Station, u32)> = scan
+            .arrivals
+            .iter()
+            .enumerate()
+            .filter(|(_, arrival)| **arrival != u32::MAX)
+            .map(|(i, arrival)| (i, arrival - departure.0))
+            .filter(|(_, duration)| *duration < limit)
+            .map(|(i, duration)| (planner.station(i), duration))
+            .collect();
+        stations.sort_by_key(|(_, duration)| *duration);
+
+        Self {
+            origin,
+            departure,
+            stations,
+        }
+    }
+    // Every reached station is surrounded by a circle for each band it falls
+    // in, with the distance in metres that can be walked in the rest of the
+    // band. Later bands come first, so that earlier ones are drawn over them.
+    pub fn circles(&self) -> impl Iterator<Item = (&Station, f64, Color32)> {
+        BANDS.into_iter().rev().flat_map(move |(minutes, color)| {
+            let limit = minutes * 60;
+            self.stations
+                .iter()
+                .filter(move |(_, duration)| *duration < limit)
+                .map(move |(station, duration)| {
+                    let radius = (limit - duration) as f64 * WALKING_SPEED;
+                    (station, radius, color)
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::planner::tests::transfer_feed;
+
+    #[test]
+    fn stations_fall_in_the_bands_they_are_reached_in() {
+        let td = transfer_feed();
+        let origin = Station::from(td.stop("a").unwrap());
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let isochrone = Isochrone::new(&td, origin, date, GtfsTime::hms(8, 0, 0), false);
+
+        let durations: Vec<(&str, u32)> = isochrone
+            .stations
+            .iter()
+            .map(|(station, duration)| (station.name.as_str(), *duration))
+            .collect();
+        // C is reached by walking from B, D only after the transfer time at B.
+        assert_eq!(durations, [("A", 0), ("B", 600), ("C", 693), ("D", 1800)]);
+
+        let circles: Vec<(&str, f64, Color32)> = isochrone
+            .circles()
+            .map(|(station, radius, color)| (station.name.as_str(), radius, color))
+            .collect();
+        let bands = |name: &str| -> Vec<Color32> {
+            circles
+                .iter()
+                .filter(|(n, _, _)| *n == name)
+                .map(|(_, _, color)| *color)
+                .collect()
+        };
+        // Stations reached at the upper limit of a band belong to the next one.
+        let colors: Vec<Color32> = BANDS.iter().rev().map(|(_, color)| *color).collect();
+        assert_eq!(bands("A"), colors);
+        assert_eq!(bands("B"), colors[..3]);
+        assert_eq!(bands("C"), colors[..3]);
+        assert_eq!(bands("D"), colors[..1]);
+
+        // Later bands are drawn first, each with the walk left in the band.
+        assert_eq!(circles[0], ("A", 2700. * WALKING_SPEED, BANDS[3].1));
+        let radius = |name: &str, band: usize| {
+            circles
+                .iter()
+                .find(|(n, _, color)| *n == name && *color == BANDS[band].1)
+                .map(|(_, radius, _)| *radius)
+        };
+        assert_eq!(radius("B", 1), Some(600. * WALKING_SPEED));
+        assert_eq!(radius("C", 2), Some(1107. * WALKING_SPEED));
+        assert_eq!(radius("D", 3), Some(900. * WALKING_SPEED));
+    }
+
+    #[test]
+    fn unknown_origin_reaches_nothing() {
+        let td = transfer_feed();
+        let origin = Station {
+            stop_id: "missing".to_string(),
+            name: "Missing".to_string(),
+            lon: 8.4,
+            lat: 49.0,
+        };
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let isochrone = Isochrone::new(&td, origin, date, GtfsTime::hms(8, 0, 0), false);
+
+        assert!(isochrone.stations.is_empty());
+        assert_eq!(isochrone.circles().count(), 0);
+    }
+}
@@ -1,15 +1,36 @@
-use egui::{Align2, Color32, ComboBox, FontId, Window, epaint::PathStroke};
+use std::{collections::HashMap, path::PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, Timelike};
+use egui::{
+    Align2, Color32, ComboBox, FontId, Grid, Label, RichText, ScrollArea, Stroke, Window,
+    epaint::PathStroke,
+};
+use egui_extras::{Column, DatePickerButton, TableBuilder};
 use walkers::{
-    Map, Plugin,
+    Map, Plugin, Projector,
     extras::{LabeledSymbol, LabeledSymbolStyle, Places, Symbol},
     lat_lon,
 };
 
 use crate::ui::{
     LichtApp,
-    state::{Element, Route},
+    export::BoundingBox,
+    gtfs::{Accessibility, Mode, SECONDS_PER_DAY},
+    isochrone::{BANDS, Isochrone},
+    planner::{Itinerary, Leg},
+    realtime::Realtime,
+    simulation::Simulation,
+    state::{Element, NearestStops, Pattern, Route, Station, palette_color, text_color_on},
 };
 
+const STATION_CLICK_RADIUS: f32 = 15.0;
+const STOP_SEARCH_RESULTS: usize = 8;
+const NEAREST_STOPS: usize = 5;
+const ISOCHRONE_OPACITY: f32 = 0.25;
+const SIMULATION_SPEEDS: [f64; 4] = [1., 10., 60., 300.];
+const TIMETABLE_STATION_WIDTH: f32 = 180.;
+const TIMETABLE_TRIP_WIDTH: f32 = 44.;
+
 pub fn show(app: &mut LichtApp, ui: &mut egui::Ui) {
     puffin::profile_function!();
 
@@ -19,31 +40,995 @@ pub fn show(app: &mut LichtApp, ui: &mut egui::Ui) {
         walkers::lon_lat(8.404418866463923, 49.01376021753036),
     );
 
-    if let Some(route) = &app.state.current_route {
-        for element in route.elements.clone() {
-            map = map.with_plugin(element);
+    if let Some(isochrone) = &app.state.isochrone.result {
+        map = map.with_plugin(IsochroneBands {
+            isochrone: isochrone.clone(),
+        });
+    }
+
+    let stations = match &app.state.current_route {
+        Some(route) => {
+            let patterns = route.patterns(app.state.current_pattern);
+            for pattern in patterns {
+                map = map.with_plugin(RouteLines {
+                    elements: pattern.elements.clone(),
+                    color: route.color,
+                });
+            }
+
+            let stations = match patterns {
+                [pattern] => pattern.stations.clone(),
+                _ => route.stations.clone(),
+            };
+            map = map.with_plugin(stops_plugin(
+                route,
+                &stations,
+                app.state.color_by_zone,
+                app.state.show_accessibility,
+            ));
+            if let Some(realtime) = &app.state.realtime {
+                map = map.with_plugin(vehicles_plugin(route, realtime));
+            }
+            stations
         }
-        map = map.with_plugin(stops_plugin(route));
+        None => Vec::new(),
+    };
+
+    if app.state.simulation.open
+        && let Some(simulation) = &app.state.simulation.simulation
+    {
+        let routes: Vec<&Route> = match &app.state.current_route {
+            Some(route) => vec![route],
+            None => app
+                .state
+                .routes
+                .iter()
+                .filter(|r| app.state.route_filter.matches(r))
+                .collect(),
+        };
+        map = map.with_plugin(simulated_vehicles_plugin(
+            simulation,
+            app.state.simulation.time,
+            &routes,
+            app.state.current_route.is_some(),
+        ));
+    }
+
+    if let Some(itinerary) = app
+        .state
+        .journey_planner
+        .itineraries
+        .as_ref()
+        .and_then(|i| i.get(app.state.journey_planner.selected))
+    {
+        map = map.with_plugin(ItineraryLines {
+            itinerary: itinerary.clone(),
+        });
     }
 
-    ui.add(map);
+    let map_response = map.show(ui, |_, projector, _| projector.clone());
+
+    let rect = map_response.response.rect;
+    let corners = [rect.left_top(), rect.right_bottom()]
+        .map(|corner| map_response.inner.unproject(corner.to_vec2()));
+    app.state.visible_area = Some(BoundingBox::new(
+        (corners[0].y(), corners[0].x()),
+        (corners[1].y(), corners[1].x()),
+    ));
+
+    if map_response.response.clicked() {
+        app.state.nearest_stops = None;
+    }
+
+    if map_response.response.clicked()
+        && let Some(pointer) = map_response.response.interact_pointer_pos()
+        && let Some(station) = clicked_station(&stations, &map_response.inner, pointer)
+    {
+        app.load_departure_board(station.clone());
+    }
+
+    if map_response.response.secondary_clicked()
+        && let Some(pointer) = map_response.response.interact_pointer_pos()
+        && let Some(transit_data) = &app.state.transit_data
+    {
+        let position = map_response.inner.unproject(pointer.to_vec2());
+        let stations = transit_data
+            .nearest_stops(position.y(), position.x(), NEAREST_STOPS)
+            .into_iter()
+            .map(|(stop, distance)| (Station::from(stop), distance))
+            .collect();
+        app.state.nearest_stops = Some(NearestStops {
+            position: pointer,
+            stations,
+        });
+    }
 
     controls(app, ui);
+    departure_board(app, ui);
+    journey_planner(app, ui);
+    feed_diff(app, ui);
+    nearest_stops(app, ui);
+    timetable(app, ui);
+    route_stats(app, ui);
+    isochrone(app, ui);
+    simulation(app, ui);
+}
+
+fn isochrone(app: &mut LichtApp, ui: &egui::Ui) {
+    if !app.state.isochrone.open {
+        return;
+    }
+
+    let mut open = true;
+    let mut compute = false;
+    Window::new("Isochrone")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_BOTTOM, [0., -10.])
+        .show(ui.ctx(), |ui| {
+            let panel = &mut app.state.isochrone;
+            Grid::new("isochrone_form").show(ui, |ui| {
+                ui.label("From");
+                ui.label(
+                    panel
+                        .origin
+                        .as_ref()
+                        .map(|s| s.name.as_str())
+                        .unwrap_or("-"),
+                );
+                ui.end_row();
+
+                ui.label("Departure");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut panel.hour).range(0..=23));
+                    ui.label(":");
+                    ui.add(egui::DragValue::new(&mut panel.minute).range(0..=59));
+                });
+                ui.end_row();
+            });
+
+            if panel.origin.is_none() {
+                ui.label(
+                    RichText::new("Click a station and choose \"Isochrone from here\"")
+                        .color(Color32::GRAY),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                let ready = panel.origin.is_some() && !panel.computing;
+                if ui
+                    .add_enabled(ready, egui::Button::new("Compute"))
+                    .clicked()
+                {
+                    compute = true;
+                }
+                if ui
+                    .add_enabled(panel.result.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    panel.result = None;
+                }
+                if panel.computing {
+                    ui.spinner();
+                }
+            });
+
+            let Some(result) = &panel.result else {
+                return;
+            };
+
+            ui.separator();
+            ui.label(format!(
+                "{} stations reached from {} at {}",
+                result.stations.len(),
+                result.origin.name,
+                result.departure
+            ));
+            ui.horizontal(|ui| {
+                for (minutes, color) in BANDS {
+                    ui.label(RichText::new("■").color(color));
+                    ui.label(format!("{minutes} min"));
+                }
+            });
+        });
+
+    if !open {
+        app.state.isochrone.open = false;
+    }
+
+    if compute {
+        app.compute_isochrone();
+    }
+}
+
+fn route_stats(app: &mut LichtApp, ui: &egui::Ui) {
+    let Some(stats) = &app.state.route_stats else {
+        return;
+    };
+
+    let mut open = true;
+    Window::new(format!("Statistics: {}", stats.route_name))
+        .id(egui::Id::new("route_stats"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ui.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                for day in &stats.days {
+                    ui.selectable_value(
+                        &mut app.state.route_stats_day,
+                        day.day_type,
+                        day.day_type.name(),
+                    );
+                }
+            });
+
+            let Some(day) = stats
+                .days
+                .iter()
+                .find(|d| d.day_type == app.state.route_stats_day)
+            else {
+                return;
+            };
+
+            ui.label(
+                RichText::new(format!("Service on {}", day.date.format("%a %d.%m.%Y")))
+                    .color(Color32::GRAY),
+            );
+            if day.directions.is_empty() {
+                ui.label("No trips on this day");
+            }
+
+            for (i, direction) in day.directions.iter().enumerate() {
+                ui.separator();
+                ui.strong(format!("→ {}", direction.headsign));
+                Grid::new(("route_stats", i)).show(ui, |ui| {
+                    ui.label("Trips");
+                    ui.label(direction.trips.to_string());
+                    ui.end_row();
+
+                    ui.label("First / last departure");
+                    ui.label(format!("{} / {}", direction.first, direction.last));
+                    ui.end_row();
+
+                    ui.label("Average headway");
+                    ui.label(minutes(direction.average_headway));
+                    ui.end_row();
+
+                    ui.label("Maximum headway");
+                    ui.label(minutes(direction.max_headway));
+                    ui.end_row();
+
+                    ui.label("Longest gap");
+                    ui.label(minutes(Some(direction.longest_gap)));
+                    ui.end_row();
+                });
+                trips_per_hour(ui, &direction.trips_per_hour);
+            }
+        });
+
+    if !open {
+        app.state.route_stats = None;
+    }
+}
+
+fn minutes(seconds: Option<u32>) -> String {
+    match seconds {
+        Some(s) => format!("{} min", (s + 30) / 60),
+        None => "-".to_string(),
+    }
+}
+
+fn trips_per_hour(ui: &mut egui::Ui, trips: &[usize]) {
+    let bar_width = 14.;
+    let height = 60.;
+    let label_height = 14.;
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(bar_width * trips.len() as f32, height + label_height),
+        egui::Sense::hover(),
+    );
+
+    let painter = ui.painter_at(rect);
+    let max = trips.iter().copied().max().unwrap_or_default().max(1);
+    let hovered = response
+        .hover_pos()
+        .map(|p| ((p.x - rect.left()) / bar_width) as usize);
+    for (hour, count) in trips.iter().enumerate() {
+        let left = rect.left() + hour as f32 * bar_width;
+        let bar_height = height * *count as f32 / max as f32;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(left + 1., rect.top() + height - bar_height),
+            egui::pos2(left + bar_width - 1., rect.top() + height),
+        );
+        let color = if hovered == Some(hour) {
+            Color32::WHITE
+        } else {
+            Color32::LIGHT_BLUE
+        };
+        painter.rect_filled(bar, 1., color);
+
+        if hour % 3 == 0 {
+            painter.text(
+                egui::pos2(left, rect.top() + height + 1.),
+                Align2::LEFT_TOP,
+                hour.to_string(),
+                FontId::proportional(10.),
+                Color32::GRAY,
+            );
+        }
+    }
+
+    if let Some(hour) = hovered
+        && let Some(count) = trips.get(hour)
+    {
+        response.on_hover_text(format!("{hour:02}:00 - {:02}:00: {count} trips", hour + 1));
+    }
+}
+
+fn timetable(app: &mut LichtApp, ui: &egui::Ui) {
+    let Some(timetable) = &app.state.timetable else {
+        return;
+    };
+
+    let mut open = true;
+    Window::new(&timetable.title)
+        .id(egui::Id::new("timetable"))
+        .open(&mut open)
+        .collapsible(false)
+        .default_size([700., 400.])
+        .show(ui.ctx(), |ui| {
+            ui.label(
+                RichText::new(format!(
+                    "{}, {} trips",
+                    timetable.date.format("%d.%m.%Y"),
+                    timetable.trips.len()
+                ))
+                .color(Color32::GRAY),
+            );
+
+            // Only the visible rows are laid out, and of the trips only the columns
+            // in view. The trips left and right of the view are stood in for by an
+            // empty column each, so that the table keeps its width.
+            ScrollArea::horizontal().show_viewport(ui, |ui, viewport| {
+                let spacing = ui.spacing().item_spacing.x;
+                let trip_width = TIMETABLE_TRIP_WIDTH + spacing;
+                let trips = timetable.trips.len();
+                let column_at = |x: f32| {
+                    let column = (x - TIMETABLE_STATION_WIDTH - spacing) / trip_width;
+                    (column.max(0.) as usize).min(trips)
+                };
+                let first = column_at(viewport.min.x).saturating_sub(1);
+                let last = (column_at(viewport.max.x) + 2).min(trips);
+                let hidden_width = |count: usize| count as f32 * trip_width - spacing;
+
+                let mut table = TableBuilder::new(ui)
+                    .striped(true)
+                    .resizable(false)
+                    .column(Column::exact(TIMETABLE_STATION_WIDTH).clip(true));
+                if first > 0 {
+                    table = table.column(Column::exact(hidden_width(first)));
+                }
+                table = table.columns(Column::exact(TIMETABLE_TRIP_WIDTH), last - first);
+                if last < trips {
+                    table = table.column(Column::exact(hidden_width(trips - last)));
+                }
+
+                table
+                    .header(20., |mut header| {
+                        header.col(|ui| {
+                            ui.strong("Station");
+                        });
+                        if first > 0 {
+                            header.col(|_| {});
+                        }
+                        for (i, trip) in timetable.trips[first..last].iter().enumerate() {
+                            header.col(|ui| {
+                                let number = first + i + 1;
+                                let label = if trip.accessible {
+                                    format!("{number} ♿")
+                                } else {
+                                    number.to_string()
+                                };
+                                ui.strong(label).on_hover_text(&trip.trip_id);
+                            });
+                        }
+                        if last < trips {
+                            header.col(|_| {});
+                        }
+                    })
+                    .body(|body| {
+                        body.rows(18., timetable.stations.len(), |mut row| {
+                            let i = row.index();
+                            row.col(|ui| {
+                                let name = &timetable.stations[i].name;
+                                ui.add(Label::new(name).truncate()).on_hover_text(name);
+                            });
+                            if first > 0 {
+                                row.col(|_| {});
+                            }
+                            for trip in &timetable.trips[first..last] {
+                                row.col(|ui| match trip.times.get(i).copied().flatten() {
+                                    Some(time) => {
+                                        ui.label(time.to_string());
+                                    }
+                                    None => {
+                                        ui.label(RichText::new("|").color(Color32::GRAY));
+                                    }
+                                });
+                            }
+                            if last < trips {
+                                row.col(|_| {});
+                            }
+                        });
+                    });
+            });
+        });
+
+    if !open {
+        app.state.timetable = None;
+    }
+}
+
+fn nearest_stops(app: &mut LichtApp, ui: &egui::Ui) {
+    let Some(nearest) = &app.state.nearest_stops else {
+        return;
+    };
+
+    let mut open = true;
+    let mut chosen = None;
+    Window::new("Nearest stops")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .fixed_pos(nearest.position)
+        .show(ui.ctx(), |ui| {
+            if nearest.stations.is_empty() {
+                ui.label(RichText::new("No stops nearby").color(Color32::GRAY));
+            }
+
+            Grid::new("nearest_stops").show(ui, |ui| {
+                for (station, distance) in &nearest.stations {
+                    if ui.link(&station.name).clicked() {
+                        chosen = Some(station.clone());
+                    }
+                    ui.label(RichText::new(format!("{distance:.0} m")).color(Color32::GRAY));
+                    ui.end_row();
+                }
+            });
+        });
+
+    if let Some(station) = chosen {
+        app.load_departure_board(station);
+        open = false;
+    }
+
+    if !open {
+        app.state.nearest_stops = None;
+    }
+}
+
+fn stop_search(app: &mut LichtApp, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.label("Find stop");
+        if ui
+            .text_edit_singleline(&mut app.state.stop_search)
+            .changed()
+        {
+            app.state.stop_search_results = match &app.state.transit_data {
+                Some(transit_data) => transit_data
+                    .search_stops(&app.state.stop_search, STOP_SEARCH_RESULTS)
+                    .into_iter()
+                    .map(Station::from)
+                    .collect(),
+                None => Vec::new(),
+            };
+        }
+    });
+
+    let mut chosen = None;
+    for station in &app.state.stop_search_results {
+        if ui
+            .selectable_label(false, &station.name)
+            .on_hover_text(&station.stop_id)
+            .clicked()
+        {
+            chosen = Some(station.clone());
+        }
+    }
+
+    if let Some(station) = chosen {
+        app.state
+            .map_memory
+            .center_at(lat_lon(station.lat, station.lon));
+        app.state.stop_search.clear();
+        app.state.stop_search_results.clear();
+        app.load_departure_board(station);
+    }
+}
+
+fn clicked_station<'a>(
+    stations: &'a [Station],
+    projector: &Projector,
+    pointer: egui::Pos2,
+) -> Option<&'a Station> {
+    stations
+        .iter()
+        .map(|s| {
+            let position = projector.project(lat_lon(s.lat, s.lon)).to_pos2();
+            (s, position.distance(pointer))
+        })
+        .filter(|(_, distance)| *distance <= STATION_CLICK_RADIUS)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(s, _)| s)
+}
+
+fn departure_board(app: &mut LichtApp, ui: &egui::Ui) {
+    let Some(board) = &app.state.departure_board else {
+        return;
+    };
+
+    let mut open = true;
+    Window::new(&board.station.name)
+        .id(egui::Id::new("departure_board"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_TOP, [-10., 10.])
+        .show(ui.ctx(), |ui| {
+            if let Some(zone) = &board.zone {
+                ui.label(format!("Zone {zone}"));
+            }
+
+            match board.accessibility {
+                Accessibility::StepFree => ui.label("♿ Step-free access"),
+                Accessibility::NotAccessible => {
+                    ui.label(RichText::new("No step-free access").color(Color32::LIGHT_RED))
+                }
+                Accessibility::Unknown => ui.label(""),
+            };
+
+            if !board.levels.is_empty() {
+                ui.label(
+                    RichText::new(format!("Levels: {}", board.levels.join(", ")))
+                        .color(Color32::GRAY),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                let planner = &mut app.state.journey_planner;
+                if ui.button("Plan from here").clicked() {
+                    planner.from = Some(board.station.clone());
+                    planner.open = true;
+                }
+                if ui.button("Plan to here").clicked() {
+                    planner.to = Some(board.station.clone());
+                    planner.open = true;
+                }
+                if ui.button("Isochrone from here").clicked() {
+                    app.state.isochrone.origin = Some(board.station.clone());
+                    app.state.isochrone.result = None;
+                    app.state.isochrone.open = true;
+                }
+            });
+
+            if board.departures.is_empty() {
+                ui.label(RichText::new("No departures").color(Color32::GRAY));
+                return;
+            }
+
+            Grid::new("departures").striped(true).show(ui, |ui| {
+                for departure in &board.departures {
+                    match departure.headway {
+                        Some(headway) => {
+                            ui.label(format!("{}, every {} min", departure.time, headway / 60))
+                        }
+                        None => ui.label(departure.time.to_string()),
+                    };
+                    let delay = app.state.realtime.as_ref().and_then(|r| {
+                        r.delay(
+                            &departure.trip_id,
+                            &board.station.stop_id,
+                            departure.stop_sequence,
+                        )
+                    });
+                    match delay {
+                        Some(delay) if delay >= 60 => ui.label(
+                            RichText::new(format!("+{}", delay / 60)).color(Color32::LIGHT_RED),
+                        ),
+                        Some(_) => ui.label(RichText::new("+0").color(Color32::LIGHT_GREEN)),
+                        None => ui.label(""),
+                    };
+                    ui.label(RichText::new(&departure.route_short_name).strong());
+                    ui.label(&departure.headsign);
+                    if departure.accessible {
+                        ui.label("♿");
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+    if !open {
+        app.state.departure_board = None;
+    }
+}
+
+fn journey_planner(app: &mut LichtApp, ui: &egui::Ui) {
+    if !app.state.journey_planner.open {
+        return;
+    }
+
+    let mut open = true;
+    let mut plan = false;
+    Window::new("Journey planner")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-10., -10.])
+        .show(ui.ctx(), |ui| {
+            let planner = &mut app.state.journey_planner;
+            Grid::new("journey_planner_form").show(ui, |ui| {
+                ui.label("From");
+                ui.label(
+                    planner
+                        .from
+                        .as_ref()
+                        .map(|s| s.name.as_str())
+                        .unwrap_or("-"),
+                );
+                ui.end_row();
+
+                ui.label("To");
+                ui.label(planner.to.as_ref().map(|s| s.name.as_str()).unwrap_or("-"));
+                ui.end_row();
+
+                ui.label("Departure");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut planner.hour).range(0..=23));
+                    ui.label(":");
+                    ui.add(egui::DragValue::new(&mut planner.minute).range(0..=59));
+                });
+                ui.end_row();
+            });
+
+            if planner.from.is_none() || planner.to.is_none() {
+                ui.label(
+                    RichText::new("Click a station to pick the start and destination")
+                        .color(Color32::GRAY),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                let ready = planner.from.is_some() && planner.to.is_some() && !planner.searching;
+                if ui.add_enabled(ready, egui::Button::new("Plan")).clicked() {
+                    plan = true;
+                }
+                if ui.button("Swap").clicked() {
+                    std::mem::swap(&mut planner.from, &mut planner.to);
+                }
+                if planner.searching {
+                    ui.spinner();
+                }
+            });
+
+            let Some(itineraries) = &planner.itineraries else {
+                return;
+            };
+
+            ui.separator();
+            if itineraries.is_empty() {
+                ui.label(RichText::new("No connections found").color(Color32::GRAY));
+                return;
+            }
+
+            for (i, itinerary) in itineraries.iter().enumerate() {
+                let summary = format!(
+                    "{} - {}, {} min, {} transfers",
+                    itinerary.departure,
+                    itinerary.arrival,
+                    (itinerary.arrival.0 - itinerary.departure.0) / 60,
+                    itinerary.transfers()
+                );
+                ui.selectable_value(&mut planner.selected, i, summary);
+            }
+
+            if let Some(itinerary) = itineraries.get(planner.selected) {
+                ui.separator();
+                itinerary_legs(itinerary, ui);
+            }
+        });
+
+    if !open {
+        app.state.journey_planner.open = false;
+    }
+
+    if plan {
+        app.plan_journey();
+    }
+}
+
+fn feed_diff(app: &mut LichtApp, ui: &egui::Ui) {
+    if !app.state.feed_diff.open {
+        return;
+    }
+
+    let mut open = true;
+    let mut compare = false;
+    Window::new("Feed diff")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::LEFT_BOTTOM, [10., -10.])
+        .show(ui.ctx(), |ui| {
+            let panel = &mut app.state.feed_diff;
+            Grid::new("feed_diff_form").show(ui, |ui| {
+                ui.label("Compare with");
+                ui.text_edit_singleline(&mut panel.source)
+                    .on_hover_text("URL, zip file or directory of the other feed version");
+                ui.end_row();
+
+                if app.feed_sources.len() > 1 {
+                    ui.label("Version of");
+                    ComboBox::from_id_salt("feed_diff_feed")
+                        .selected_text(
+                            app.feed_sources
+                                .get(panel.feed)
+                                .map(|f| f.to_string())
+                                .unwrap_or_default(),
+                        )
+                        .show_ui(ui, |ui| {
+                            for (i, feed) in app.feed_sources.iter().enumerate() {
+                                ui.selectable_value(&mut panel.feed, i, feed.to_string());
+                            }
+                        });
+                    ui.end_row();
+                }
+
+                ui.label("Moved stops over");
+                ui.add(
+                    egui::DragValue::new(&mut panel.moved_distance)
+                        .range(1.0..=10_000.0)
+                        .suffix(" m"),
+                );
+                ui.end_row();
+
+                ui.label("Trips per day for");
+                ui.add(
+                    egui::DragValue::new(&mut panel.days)
+                        .range(1..=60)
+                        .suffix(" days"),
+                );
+                ui.end_row();
+            });
+
+            ui.horizontal(|ui| {
+                let ready = !panel.source.trim().is_empty() && !panel.running;
+                if ui
+                    .add_enabled(ready, egui::Button::new("Compare"))
+                    .clicked()
+                {
+                    compare = true;
+                }
+                if panel.running {
+                    ui.spinner();
+                }
+            });
+
+            let diff = match &panel.diff {
+                Some(Ok(diff)) => diff,
+                Some(Err(err)) => {
+                    ui.label(RichText::new(err.to_string()).color(Color32::LIGHT_RED));
+                    return;
+                }
+                None => return,
+            };
+
+            ui.separator();
+            ui.label(format!("{} → {}", diff.old, diff.new));
+            if diff.is_empty() {
+                ui.label(RichText::new("No changes").color(Color32::GRAY));
+            }
+
+            ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                feed_diff_section(ui, "Added routes", &diff.added_routes, |r| r.name.clone());
+                feed_diff_section(ui, "Removed routes", &diff.removed_routes, |r| {
+                    r.name.clone()
+                });
+                feed_diff_section(ui, "Moved stops", &diff.moved_stops, |s| {
+                    format!("{}: {:.0} m", s.name, s.distance)
+                });
+                feed_diff_section(ui, "Renamed stops", &diff.renamed_stops, |s| {
+                    format!("{} → {}", s.old_name, s.new_name)
+                });
+                feed_diff_section(ui, "Changed stop sequences", &diff.changed_patterns, |c| {
+                    format!(
+                        "{}: {} removed, {} added",
+                        c.route.name,
+                        c.removed.len(),
+                        c.added.len()
+                    )
+                });
+                feed_diff_section(ui, "Trips per day", &diff.trip_counts, |c| {
+                    format!(
+                        "{} {}: {} → {}",
+                        c.date.format("%d.%m."),
+                        c.route.name,
+                        c.old,
+                        c.new
+                    )
+                });
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut panel.path);
+            });
+            ui.horizontal(|ui| {
+                let mut save = |extension: &str, content: String| {
+                    let path = PathBuf::from(&panel.path).with_extension(extension);
+                    panel.saved = Some(
+                        std::fs::write(&path, content)
+                            .map(|_| path)
+                            .map_err(|err| err.to_string()),
+                    );
+                };
+                if ui.button("Save JSON").clicked() {
+                    save("json", diff.to_json());
+                }
+                if ui.button("Save Markdown").clicked() {
+                    save("md", diff.to_markdown());
+                }
+            });
+
+            match &panel.saved {
+                Some(Ok(path)) => {
+                    ui.label(
+                        RichText::new(format!("Saved {}", path.display()))
+                            .color(Color32::LIGHT_GREEN),
+                    );
+                }
+                Some(Err(err)) => {
+                    ui.label(RichText::new(err).color(Color32::LIGHT_RED));
+                }
+                None => {}
+            }
+        });
+
+    if !open {
+        app.state.feed_diff.open = false;
+    }
+
+    if compare {
+        app.compare_feeds();
+    }
+}
+
+fn feed_diff_section<T>(ui: &mut egui::Ui, title: &str, items: &[T], label: impl Fn(&T) -> String) {
+    if items.is_empty() {
+        return;
+    }
+
+    ui.collapsing(format!("{title} ({})", items.len()), |ui| {
+        for item in items {
+            ui.label(label(item));
+        }
+    });
+}
+
+fn itinerary_legs(itinerary: &Itinerary, ui: &mut egui::Ui) {
+    Grid::new("itinerary").striped(true).show(ui, |ui| {
+        for leg in &itinerary.legs {
+            match leg {
+                Leg::Ride {
+                    route_short_name,
+                    headsign,
+                    color,
+                    stations,
+                    departure,
+                    arrival,
+                    fare,
+                } => {
+                    ui.label(format!("{departure} - {arrival}"));
+                    ui.label(RichText::new(route_short_name).strong().color(*color));
+                    ui.label(format!(
+                        "{} to {} (towards {headsign})",
+                        stations[0].name,
+                        stations[stations.len() - 1].name
+                    ));
+                    if let Some(fare) = fare {
+                        ui.label(RichText::new(fare.to_string()).color(Color32::GRAY));
+                    }
+                }
+                Leg::Walk {
+                    from,
+                    to,
+                    departure,
+                    arrival,
+                } => {
+                    ui.label(format!("{departure} - {arrival}"));
+                    ui.label("Walk");
+                    ui.label(format!("{} to {}", from.name, to.name));
+                }
+                Leg::Wait { station, duration } => {
+                    ui.label("");
+                    ui.label(RichText::new("Wait").color(Color32::GRAY));
+                    ui.label(
+                        RichText::new(format!("{} min at {}", duration / 60, station.name))
+                            .color(Color32::GRAY),
+                    );
+                }
+            }
+            ui.end_row();
+        }
+    });
 }
 
-fn stops_plugin(route: &Route) -> impl Plugin {
+// Stations are drawn in the route colour, or in the colour of their fare zone.
+fn stops_plugin(
+    route: &Route,
+    stations: &[Station],
+    color_by_zone: bool,
+    show_accessibility: bool,
+) -> impl Plugin + use<> {
     puffin::profile_function!();
 
-    let places = route
-        .stations
+    let places = stations
+        .iter()
+        .map(|s| {
+            let marker = route.markers.get(&s.stop_id);
+            let zone = marker
+                .and_then(|m| m.zone.as_ref())
+                .filter(|_| color_by_zone);
+            let (label, color, text_color) = match zone {
+                Some(zone) => {
+                    let color = palette_color(zone);
+                    (format!("{} ({zone})", s.name), color, text_color_on(color))
+                }
+                None if color_by_zone => (s.name.clone(), Color32::GRAY, Color32::BLACK),
+                None => (s.name.clone(), route.color, route.text_color),
+            };
+
+            let symbol = if show_accessibility && marker.is_some_and(|m| m.step_free) {
+                Symbol::TwoCorners("♿".to_string())
+            } else {
+                Symbol::Circle("🚆".to_string())
+            };
+
+            LabeledSymbol {
+                position: walkers::lat_lon(s.lat, s.lon),
+                label,
+                symbol: Some(symbol),
+                style: LabeledSymbolStyle {
+                    label_font: FontId::proportional(12.0),
+                    label_color: text_color,
+                    label_background: color.gamma_multiply(0.8),
+                    symbol_size: 20.,
+                    symbol_stroke: Stroke::new(2., color),
+                    ..Default::default()
+                },
+            }
+        })
+        .collect();
+
+    Places::new(places)
+}
+
+fn vehicles_plugin(route: &Route, realtime: &Realtime) -> impl Plugin {
+    let places = realtime
+        .vehicles
         .iter()
-        .map(|s| LabeledSymbol {
-            position: walkers::lat_lon(s.lat, s.lon),
-            label: s.name.to_string(),
-            symbol: Some(Symbol::Circle("🚆".to_string())),
+        .filter(|v| v.route_id == route.route_id)
+        .map(|v| LabeledSymbol {
+            position: walkers::lat_lon(v.lat, v.lon),
+            label: v.label.clone(),
+            symbol: Some(Symbol::TwoCorners("🚋".to_string())),
             style: LabeledSymbolStyle {
-                label_font: FontId::proportional(12.0),
+                label_font: FontId::proportional(10.0),
                 symbol_size: 20.,
+                symbol_background: route.color,
+                symbol_color: route.text_color,
                 ..Default::default()
             },
         })
@@ -52,6 +1037,125 @@ fn stops_plugin(route: &Route) -> impl Plugin {
     Places::new(places)
 }
 
+// Vehicles where the schedule places them, for the routes shown on the map.
+fn simulated_vehicles_plugin(
+    simulation: &Simulation,
+    time: f64,
+    routes: &[&Route],
+    labels: bool,
+) -> impl Plugin + use<> {
+    let routes: HashMap<&str, &Route> = routes.iter().map(|r| (r.route_id.as_str(), *r)).collect();
+
+    let places = simulation
+        .vehicles(time)
+        .filter_map(|v| {
+            let route = routes.get(v.route_id)?;
+            Some(LabeledSymbol {
+                position: walkers::lat_lon(v.lat, v.lon),
+                // Headsigns only fit on the map for a single route.
+                label: match labels {
+                    true => v.headsign.to_string(),
+                    false => String::new(),
+                },
+                symbol: Some(Symbol::Circle(route.name.clone())),
+                style: LabeledSymbolStyle {
+                    label_font: FontId::proportional(10.0),
+                    symbol_font: FontId::proportional(9.0),
+                    symbol_size: 18.,
+                    symbol_background: route.color,
+                    symbol_color: route.text_color,
+                    ..Default::default()
+                },
+            })
+        })
+        .collect();
+
+    Places::new(places)
+}
+
+fn simulation(app: &mut LichtApp, ui: &egui::Ui) {
+    let panel = &mut app.state.simulation;
+    if !panel.open {
+        panel.running = false;
+        return;
+    }
+
+    if panel.running {
+        panel.time += ui.input(|i| i.stable_dt) as f64 * panel.speed;
+        // The clock starts over at the end of the service day.
+        panel.time %= SECONDS_PER_DAY as f64;
+        ui.ctx().request_repaint();
+    }
+
+    let outdated = panel
+        .simulation
+        .as_ref()
+        .is_none_or(|s| s.date != app.state.service_date);
+    if outdated && !panel.loading {
+        app.load_simulation();
+    }
+
+    let mut open = true;
+    Window::new("Simulation")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_TOP, [0., 10.])
+        .show(ui.ctx(), |ui| {
+            let panel = &mut app.state.simulation;
+            ui.horizontal(|ui| {
+                let seconds = panel.time as u32;
+                ui.heading(format!(
+                    "{:02}:{:02}:{:02}",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                ));
+
+                let label = if panel.running { "Pause" } else { "Play" };
+                if ui.button(label).clicked() {
+                    panel.running = !panel.running;
+                }
+                if ui.button("Now").clicked() {
+                    panel.time = Local::now().num_seconds_from_midnight() as f64;
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Speed");
+                for speed in SIMULATION_SPEEDS {
+                    ui.selectable_value(&mut panel.speed, speed, format!("{speed}×"));
+                }
+            });
+
+            ui.add(
+                egui::Slider::new(&mut panel.time, 0.0..=(SECONDS_PER_DAY - 1) as f64)
+                    .show_value(false)
+                    .text("Time"),
+            );
+
+            match &panel.simulation {
+                Some(simulation) if !outdated => {
+                    ui.label(
+                        RichText::new(format!(
+                            "{} trips running on {}",
+                            simulation.vehicles(panel.time).count(),
+                            simulation.date.format("%d.%m.%Y")
+                        ))
+                        .color(Color32::GRAY),
+                    );
+                }
+                _ => {
+                    ui.spinner();
+                }
+            }
+        });
+
+    if !open {
+        app.state.simulation.open = false;
+    }
+}
+
 fn controls(app: &mut LichtApp, ui: &egui::Ui) {
     Window::new("Controls")
         .collapsible(false)
@@ -59,32 +1163,368 @@ fn controls(app: &mut LichtApp, ui: &egui::Ui) {
         .title_bar(false)
         .anchor(Align2::LEFT_TOP, [10., 10.])
         .show(ui.ctx(), |ui| {
-            if ui.button("Back").clicked() {
-                app.state.show_map = false;
-            }
+            ui.horizontal(|ui| {
+                if ui.button("Back").clicked() {
+                    app.state.show_map = false;
+                }
+
+                ui.toggle_value(&mut app.state.journey_planner.open, "Journey planner");
+                ui.toggle_value(&mut app.state.feed_diff.open, "Feed diff");
+                ui.toggle_value(&mut app.state.isochrone.open, "Isochrone");
+                ui.toggle_value(&mut app.state.simulation.open, "Simulation");
+            });
 
             ui.separator();
 
-            let selected_text: &str = match &app.state.current_route {
-                Some(r) => &r.name,
-                None => "",
+            ui.horizontal(|ui| {
+                ui.label("Date");
+                if ui
+                    .add(DatePickerButton::new(&mut app.state.service_date))
+                    .changed()
+                {
+                    app.reload_routes();
+                }
+            });
+
+            stop_search(app, ui);
+
+            let selected_text = match &app.state.current_route {
+                Some(r) => r.label.clone(),
+                None => String::new(),
             };
 
+            let mut load_route_stats = false;
             ComboBox::from_label("Route")
                 .selected_text(selected_text)
                 .show_ui(ui, |ui| {
-                    for route in &app.state.routes {
-                        ui.selectable_value(
-                            &mut app.state.current_route,
-                            Some(route.clone()),
-                            &route.name,
-                        );
+                    for route in app
+                        .state
+                        .routes
+                        .iter()
+                        .filter(|r| app.state.route_filter.matches(r))
+                    {
+                        if ui
+                            .selectable_value(
+                                &mut app.state.current_route,
+                                Some(route.clone()),
+                                route.label.clone(),
+                            )
+                            .changed()
+                        {
+                            app.state.current_pattern = None;
+                            app.state.timetable = None;
+                            // Open statistics follow the chosen route.
+                            load_route_stats = app.state.route_stats.is_some();
+                        }
                     }
                 });
+
+            let mut load_timetable = false;
+            if let Some(route) = &app.state.current_route {
+                let previous_pattern = app.state.current_pattern;
+                let selected_text = match app.state.current_pattern {
+                    Some(i) => pattern_label(route, &route.patterns[i]),
+                    None => "All directions".to_string(),
+                };
+
+                ComboBox::from_label("Direction")
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut app.state.current_pattern, None, "All directions");
+                        for (i, pattern) in route.patterns.iter().enumerate() {
+                            ui.selectable_value(
+                                &mut app.state.current_pattern,
+                                Some(i),
+                                format!(
+                                    "{} ({} trips)",
+                                    pattern_label(route, pattern),
+                                    pattern.trip_count
+                                ),
+                            );
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            app.state.current_pattern.is_some(),
+                            egui::Button::new("Timetable"),
+                        )
+                        .on_disabled_hover_text("Choose a direction first")
+                        .clicked()
+                    {
+                        load_timetable = true;
+                    }
+                    if ui.button("Statistics").clicked() {
+                        load_route_stats = true;
+                    }
+                });
+
+                // An open timetable follows the chosen direction.
+                if app.state.timetable.is_some() && app.state.current_pattern != previous_pattern {
+                    match app.state.current_pattern {
+                        Some(_) => load_timetable = true,
+                        None => app.state.timetable = None,
+                    }
+                }
+            }
+            if load_timetable {
+                app.load_timetable();
+            }
+            if load_route_stats {
+                app.load_route_stats();
+            }
+
+            ui.checkbox(&mut app.state.color_by_zone, "Colour stations by fare zone");
+            ui.checkbox(&mut app.state.show_accessibility, "Show step-free stations");
+            if ui
+                .checkbox(&mut app.state.accessible_only, "Accessible trips only")
+                .changed()
+                && let Some(board) = &app.state.departure_board
+            {
+                app.load_departure_board(board.station.clone());
+            }
+
+            if let Some(route) = &app.state.current_route {
+                let service = match route.headway {
+                    Some(headway) => {
+                        format!("{} trips, every {} min", route.trip_count, headway / 60)
+                    }
+                    None => format!("{} trips", route.trip_count),
+                };
+                ui.label(RichText::new(service).color(Color32::GRAY));
+            }
+
+            route_filter(app, ui);
+            export(app, ui);
+            feed_validity(app, ui);
+            realtime_status(app, ui);
+            feed_errors(app, ui);
+        });
+}
+
+// Variants with the same headsign are told apart by where they start, a
+// station only they serve or their number of stops.
+fn pattern_label(route: &Route, pattern: &Pattern) -> String {
+    let label = format!("{} → {}", route.name, pattern.headsign);
+    let variants: Vec<&Pattern> = route
+        .patterns
+        .iter()
+        .filter(|p| {
+            !std::ptr::eq(*p, pattern)
+                && p.direction_id == pattern.direction_id
+                && p.headsign == pattern.headsign
+        })
+        .collect();
+    if variants.is_empty() {
+        return label;
+    }
+
+    let serves =
+        |p: &Pattern, station: &Station| p.stations.iter().any(|s| s.stop_id == station.stop_id);
+    if let Some(first) = pattern.stations.first()
+        && variants.iter().all(|v| {
+            v.stations
+                .first()
+                .is_none_or(|s| s.stop_id != first.stop_id)
+        })
+    {
+        return format!("{label}, from {}", first.name);
+    }
+    if let Some(station) = pattern
+        .stations
+        .iter()
+        .find(|s| variants.iter().all(|v| !serves(v, s)))
+    {
+        return format!("{label}, via {}", station.name);
+    }
+
+    format!("{label}, {} stops", pattern.stations.len())
+}
+
+fn feed_validity(app: &LichtApp, ui: &mut egui::Ui) {
+    let Some(transit_data) = &app.state.transit_data else {
+        return;
+    };
+
+    let today = Local::now().date_naive();
+    let format = |date: Option<NaiveDate>| {
+        date.map(|d| d.format("%d.%m.%Y").to_string())
+            .unwrap_or_else(|| "?".to_string())
+    };
+
+    for feed in &transit_data.feeds {
+        let name = feed.name();
+        let validity = format!(
+            "valid {} - {}",
+            format(feed.valid_from),
+            format(feed.valid_until)
+        );
+
+        if feed.is_valid_on(today) {
+            ui.label(format!("{name}, {validity}"))
+                .on_hover_text(&feed.source);
+        } else {
+            ui.label(
+                RichText::new(format!("{name} is not valid today, {validity}"))
+                    .color(Color32::LIGHT_RED),
+            )
+            .on_hover_text(&feed.source);
+        }
+    }
+}
+
+fn realtime_status(app: &LichtApp, ui: &mut egui::Ui) {
+    if let Some(err) = &app.state.realtime_error {
+        ui.label(RichText::new(format!("Realtime: {err}")).color(Color32::LIGHT_RED));
+    }
+
+    let Some(realtime) = &app.state.realtime else {
+        return;
+    };
+
+    let updated = DateTime::from_timestamp(realtime.timestamp as i64, 0)
+        .map(|t| t.with_timezone(&Local).format("%H:%M:%S").to_string())
+        .unwrap_or_default();
+    ui.label(format!(
+        "Realtime {updated}: {} vehicles, {} trip updates",
+        realtime.vehicles.len(),
+        realtime.trip_update_count()
+    ));
+
+    if realtime.unmatched_trips > 0 {
+        ui.label(
+            RichText::new(format!(
+                "{} realtime trips not in schedule",
+                realtime.unmatched_trips
+            ))
+            .color(Color32::GRAY),
+        );
+    }
+}
+
+fn feed_errors(app: &LichtApp, ui: &mut egui::Ui) {
+    let Some(transit_data) = &app.state.transit_data else {
+        return;
+    };
+
+    if transit_data.errors.is_empty() {
+        return;
+    }
+
+    let title = RichText::new(format!("Feed errors ({})", transit_data.errors.len()))
+        .color(Color32::LIGHT_RED);
+    ui.collapsing(title, |ui| {
+        ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+            for err in &transit_data.errors {
+                ui.label(RichText::new(err.to_string()).color(Color32::LIGHT_RED));
+            }
+        });
+    });
+}
+
+fn route_filter(app: &mut LichtApp, ui: &mut egui::Ui) {
+    ui.collapsing("Filter", |ui| {
+        if let Some(transit_data) = &app.state.transit_data {
+            let agency_ids: Vec<&str> = transit_data
+                .agencies
+                .iter()
+                .map(|a| a.agency_id.as_str())
+                .collect();
+
+            for agency in &transit_data.agencies {
+                let mut show = app.state.route_filter.shows_agency(&agency.agency_id);
+                if ui.checkbox(&mut show, &agency.agency_name).changed() {
+                    app.state
+                        .route_filter
+                        .set_agency(&agency.agency_id, show, &agency_ids);
+                }
+            }
+        }
+
+        ui.separator();
+
+        for mode in Mode::ALL
+            .into_iter()
+            .filter(|m| app.state.routes.iter().any(|r| r.mode == *m))
+        {
+            let mut show = app.state.route_filter.modes.contains(&mode);
+            if ui.checkbox(&mut show, mode.name()).changed() {
+                app.state.route_filter.set_mode(mode, show);
+            }
+        }
+    });
+}
+
+fn export(app: &mut LichtApp, ui: &mut egui::Ui) {
+    let mut start = false;
+    ui.collapsing("Export GTFS", |ui| {
+        let export = &mut app.state.export;
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut export.path);
         });
+        ui.horizontal(|ui| {
+            ui.label("Days from service date");
+            ui.add(egui::DragValue::new(&mut export.days).range(1..=366));
+        });
+        ui.checkbox(&mut export.selected_route_only, "Selected route only");
+        ui.checkbox(&mut export.visible_area_only, "Visible area only");
+
+        ui.horizontal(|ui| {
+            let ready = app.state.transit_data.is_some() && !export.running;
+            if ui.add_enabled(ready, egui::Button::new("Export")).clicked() {
+                start = true;
+            }
+            if export.running {
+                ui.spinner();
+            }
+        });
+
+        match &export.result {
+            Some(Ok(summary)) => {
+                ui.label(RichText::new(format!("Wrote {summary}")).color(Color32::LIGHT_GREEN));
+            }
+            Some(Err(err)) => {
+                ui.label(RichText::new(err.to_string()).color(Color32::LIGHT_RED));
+            }
+            None => {}
+        }
+    });
+
+    if start {
+        app.export_subset();
+    }
+}
+
+struct IsochroneBands {
+    isochrone: Isochrone,
+}
+
+impl Plugin for IsochroneBands {
+    fn run(
+        self: Box<Self>,
+        ui: &mut egui::Ui,
+        _response: &egui::Response,
+        projector: &walkers::Projector,
+        _map_memory: &walkers::MapMemory,
+    ) {
+        for (station, radius, color) in self.isochrone.circles() {
+            let position = lat_lon(station.lat, station.lon);
+            ui.painter().circle_filled(
+                projector.project(position).to_pos2(),
+                radius as f32 * projector.scale_pixel_per_meter(position),
+                color.gamma_multiply(ISOCHRONE_OPACITY),
+            );
+        }
+    }
+}
+
+struct ItineraryLines {
+    itinerary: Itinerary,
 }
 
-impl Plugin for Element {
+impl Plugin for ItineraryLines {
     fn run(
         self: Box<Self>,
         ui: &mut egui::Ui,
@@ -92,15 +1532,67 @@ impl Plugin for Element {
         projector: &walkers::Projector,
         _map_memory: &walkers::MapMemory,
     ) {
-        let mut positions = Vec::new();
-        for geometry in self.geometry {
-            let position = projector
-                .project(lat_lon(geometry.lat, geometry.lon))
-                .to_pos2();
-            positions.push(position);
+        let project = |station: &Station| {
+            projector
+                .project(lat_lon(station.lat, station.lon))
+                .to_pos2()
+        };
+
+        for leg in &self.itinerary.legs {
+            match leg {
+                Leg::Ride {
+                    stations, color, ..
+                } => {
+                    let positions: Vec<egui::Pos2> = stations.iter().map(project).collect();
+                    ui.painter()
+                        .line(positions.clone(), PathStroke::new(6.0, *color));
+                    for position in positions {
+                        ui.painter().circle(
+                            position,
+                            4.0,
+                            Color32::WHITE,
+                            Stroke::new(2.0, *color),
+                        );
+                    }
+                }
+                Leg::Walk { from, to, .. } => {
+                    ui.painter().add(egui::Shape::dashed_line(
+                        &[project(from), project(to)],
+                        Stroke::new(3.0, Color32::LIGHT_GRAY),
+                        6.0,
+                        4.0,
+                    ));
+                }
+                Leg::Wait { .. } => {}
+            }
         }
+    }
+}
+
+struct RouteLines {
+    elements: Vec<Element>,
+    color: Color32,
+}
 
-        ui.painter()
-            .line(positions, PathStroke::new(5.0, Color32::RED));
+impl Plugin for RouteLines {
+    fn run(
+        self: Box<Self>,
+        ui: &mut egui::Ui,
+        _response: &egui::Response,
+        projector: &walkers::Projector,
+        _map_memory: &walkers::MapMemory,
+    ) {
+        for element in self.elements {
+            let mut positions = Vec::new();
+            for geometry in element.geometry {
+                let position = projector
+                    .project(lat_lon(geometry.lat, geometry.lon))
+                    .to_pos2();
+                positions.push(position);
+            }
+
+            ui.painter()
+                .line(positions, PathStroke::new(5.0, self.color));
+        }
     }
 }
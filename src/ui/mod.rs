@@ -1,29 +1,64 @@
-use crate::{Config, ui::gtfs::TransitData};
+use crate::{
+    Config,
+    ui::gtfs::{FeedConfig, FeedSource, GtfsError, GtfsTime, TransitData},
+};
+use cache::Cache;
+use chrono::{Days, Local, NaiveDate, Timelike};
+use diff::{DiffOptions, FeedDiff};
 use egui::{
     Image, ImageButton, RichText, ScrollArea,
     TextStyle::{Body, Button, Heading},
 };
-use state::{MovieDetails, MovieSearch, State, StateMutation};
+use export::ExportFilter;
+use isochrone::Isochrone;
+use planner::Planner;
+use simulation::Simulation;
+use state::{
+    DepartureBoard, MovieDetails, MovieSearch, RouteFilter, State, StateMutation, Station,
+};
+use stats::RouteStats;
 use std::{
-    sync::mpsc::{Receiver, Sender},
-    time::Instant,
+    path::PathBuf,
+    sync::{
+        Arc,
+        mpsc::{Receiver, Sender},
+    },
+    time::{Duration, Instant},
 };
+use timetable::Timetable;
 use tmdb::TmdbClient;
 use tokio::runtime::{Builder, Runtime};
+use tracing::{info, warn};
 
 use egui::{Color32, FontId};
 
-mod gtfs;
+pub mod cache;
+mod diff;
+pub mod export;
+mod fares;
+pub mod gtfs;
+mod isochrone;
 mod map;
+mod planner;
+pub mod realtime;
+mod search;
+mod simulation;
 mod state;
+mod stats;
+mod timetable;
 mod tmdb;
 
+const DEPARTURE_BOARD_SIZE: usize = 15;
+const FEED_DIFF_DAYS: u64 = 7;
+
 pub struct LichtApp {
     tmdb_client: TmdbClient,
+    cache: Cache,
     rt: Runtime,
     rx: Receiver<StateMutation>,
     tx: Sender<StateMutation>,
     state: State,
+    feed_sources: Vec<FeedConfig>,
 }
 
 impl eframe::App for LichtApp {
@@ -69,22 +104,328 @@ impl LichtApp {
         let (tx, rx) = std::sync::mpsc::channel();
         let rt = Builder::new_multi_thread().enable_all().build().unwrap();
 
+        let route_filter = RouteFilter::new(config.agencies, config.modes);
+        let state = State::new(config.mapbox_token, route_filter, ctx);
+        let cache = Cache::new(config.cache_dir);
+
         let t = tx.clone();
+        let c = cache.clone();
+        let feed_sources = config.gtfs_sources;
+        let gtfs_sources = feed_sources.clone();
+        let service_date = state.service_date;
         rt.spawn(async move {
-            let transit_data = TransitData::load().await;
-            let routes = state::load_routes(&transit_data).await;
+            let cached = c.transit_data().map(Arc::new);
+            if let Some(transit_data) = &cached {
+                t.send(state::transit_data_mutation(transit_data.clone()))
+                    .unwrap();
+                let routes = match c.routes(service_date) {
+                    Some(routes) => routes,
+                    None => load_routes(&c, transit_data, service_date).await,
+                };
+                t.send(state::routes_mutation(routes)).unwrap();
+            }
+
+            let transit_data = match &cached {
+                Some(cached) => TransitData::load_if_changed(&gtfs_sources, cached).await,
+                None => Some(TransitData::load(&gtfs_sources).await),
+            };
+
+            let Some(transit_data) = transit_data else {
+                info!("feeds unchanged, using cache");
+                return;
+            };
+
+            c.store_transit_data(&transit_data);
+            // Changes of an updated feed are shown right away.
+            if let Some(cached) = &cached {
+                let options = DiffOptions {
+                    moved_distance: diff::DEFAULT_MOVED_DISTANCE,
+                    from: service_date,
+                    days: FEED_DIFF_DAYS,
+                };
+                let diff = FeedDiff::new(cached, &transit_data, &options);
+                let open = !diff.is_empty();
+                t.send(state::feed_diff_mutation(Ok(diff), open)).unwrap();
+            }
+            let transit_data = Arc::new(transit_data);
+            t.send(state::transit_data_mutation(transit_data.clone()))
+                .unwrap();
+            let routes = load_routes(&c, &transit_data, service_date).await;
             t.send(state::routes_mutation(routes)).unwrap();
         });
 
+        if let Some(source) = config.realtime_source {
+            let interval = config
+                .realtime_interval_secs
+                .map(Duration::from_secs)
+                .unwrap_or(realtime::DEFAULT_POLL_INTERVAL);
+            let namespace = config.realtime_namespace;
+            let t = tx.clone();
+            rt.spawn(async move {
+                loop {
+                    let mut feed = source.fetch().await;
+                    if let (Ok(feed), Some(namespace)) = (&mut feed, &namespace) {
+                        realtime::namespace(feed, namespace);
+                    }
+                    if let Err(err) = &feed {
+                        warn!("{err}");
+                    }
+                    t.send(state::realtime_mutation(feed)).unwrap();
+                    tokio::time::sleep(interval).await;
+                }
+            });
+        }
+
         Self {
             tmdb_client: TmdbClient::new(config.tmdb_token),
+            cache,
             rt,
             tx,
             rx,
-            state: State::new(config.mapbox_token, ctx),
+            state,
+            feed_sources,
         }
     }
 
+    fn reload_routes(&self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+
+        let cache = self.cache.clone();
+        let service_date = self.state.service_date;
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let routes = load_routes(&cache, &transit_data, service_date).await;
+            tx.send(state::routes_mutation(routes)).unwrap();
+        });
+    }
+
+    fn load_departure_board(&self, station: Station) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+
+        let service_date = self.state.service_date;
+        let now = Local::now();
+        let after = if now.date_naive() == service_date {
+            GtfsTime::hms(now.hour(), now.minute(), now.second())
+        } else {
+            GtfsTime(0)
+        };
+
+        let accessible_only = self.state.accessible_only;
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let departures = transit_data.departures(
+                &station.stop_id,
+                service_date,
+                after,
+                DEPARTURE_BOARD_SIZE,
+                accessible_only,
+            );
+            let zone = fares::zone(&transit_data, &station.stop_id);
+            let accessibility = transit_data.accessibility(&station.stop_id);
+            let levels = transit_data
+                .levels_at(&station.stop_id)
+                .into_iter()
+                .map(|l| {
+                    l.level_name
+                        .clone()
+                        .unwrap_or_else(|| l.level_index.to_string())
+                })
+                .collect();
+            tx.send(state::departure_board_mutation(DepartureBoard {
+                station,
+                zone,
+                accessibility,
+                levels,
+                departures,
+            }))
+            .unwrap();
+        });
+    }
+
+    fn plan_journey(&mut self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+
+        let planner = &mut self.state.journey_planner;
+        let (Some(from), Some(to)) = (planner.from.clone(), planner.to.clone()) else {
+            return;
+        };
+
+        planner.searching = true;
+        let departure = planner.departure();
+        let service_date = self.state.service_date;
+        let accessible_only = self.state.accessible_only;
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let planner = Planner::new(&transit_data, service_date, accessible_only);
+            let itineraries = planner.plan(&from.stop_id, &to.stop_id, departure);
+            tx.send(state::itineraries_mutation(itineraries)).unwrap();
+        });
+    }
+
+    fn compute_isochrone(&mut self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+
+        let panel = &mut self.state.isochrone;
+        let Some(origin) = panel.origin.clone() else {
+            return;
+        };
+
+        panel.computing = true;
+        let departure = panel.departure();
+        let service_date = self.state.service_date;
+        let accessible_only = self.state.accessible_only;
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let isochrone = Isochrone::new(
+                &transit_data,
+                origin,
+                service_date,
+                departure,
+                accessible_only,
+            );
+            tx.send(state::isochrone_mutation(isochrone)).unwrap();
+        });
+    }
+
+    fn load_timetable(&self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+        let Some(route) = self.state.current_route.clone() else {
+            return;
+        };
+        let Some(pattern) = route.patterns(self.state.current_pattern).first().cloned() else {
+            return;
+        };
+
+        let service_date = self.state.service_date;
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let timetable = Timetable::new(&transit_data, &route, &pattern, service_date);
+            tx.send(state::timetable_mutation(timetable)).unwrap();
+        });
+    }
+
+    fn load_simulation(&mut self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+
+        self.state.simulation.loading = true;
+        let service_date = self.state.service_date;
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let simulation = Simulation::new(&transit_data, service_date);
+            tx.send(state::simulation_mutation(Arc::new(simulation)))
+                .unwrap();
+        });
+    }
+
+    fn load_route_stats(&self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+        let Some(route) = self.state.current_route.clone() else {
+            return;
+        };
+
+        let service_date = self.state.service_date;
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let stats = RouteStats::new(&transit_data, &route, service_date);
+            tx.send(state::route_stats_mutation(stats)).unwrap();
+        });
+    }
+
+    // Exports the routes shown on the map, from the service date on.
+    fn export_subset(&mut self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+
+        let export = &mut self.state.export;
+        let routes = match &self.state.current_route {
+            Some(route) if export.selected_route_only => vec![route.route_id.clone()],
+            _ => self
+                .state
+                .routes
+                .iter()
+                .filter(|r| self.state.route_filter.matches(r))
+                .map(|r| r.route_id.clone())
+                .collect(),
+        };
+        let from = self.state.service_date;
+        let until = from + Days::new(export.days.saturating_sub(1));
+        let filter = ExportFilter {
+            routes,
+            agencies: Vec::new(),
+            bounding_box: self.state.visible_area.filter(|_| export.visible_area_only),
+            dates: Some((from, until)),
+        };
+
+        export.running = true;
+        export.result = None;
+        let path = PathBuf::from(&export.path);
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            let result = export::export(&transit_data, &filter, &path);
+            tx.send(state::export_mutation(result)).unwrap();
+        });
+    }
+
+    // Compares the loaded feeds, as the old version, with another feed.
+    fn compare_feeds(&mut self) {
+        let Some(transit_data) = self.state.transit_data.clone() else {
+            return;
+        };
+
+        let panel = &mut self.state.feed_diff;
+        let source = panel.source.trim().to_string();
+        if source.is_empty() {
+            return;
+        }
+
+        panel.running = true;
+        let options = DiffOptions {
+            moved_distance: panel.moved_distance,
+            from: self.state.service_date,
+            days: panel.days,
+        };
+        let feed = self.feed_sources.get(panel.feed).cloned();
+        let tx = self.tx.clone();
+        self.rt.spawn(async move {
+            // IDs of the other version are namespaced like the feed it replaces,
+            // and only that feed is compared with it.
+            let config = FeedConfig {
+                source: FeedSource::from(source.clone()),
+                namespace: feed.as_ref().and_then(|f| f.namespace.clone()),
+            };
+            let old = match &feed {
+                Some(feed) if transit_data.feeds.len() > 1 => transit_data.feed(feed),
+                _ => None,
+            };
+            let old = old.as_ref().unwrap_or(&transit_data);
+            let other = TransitData::load(&[config]).await;
+            let diff = if other.feeds.is_empty() {
+                Err(other.errors.first().cloned().unwrap_or(GtfsError::File {
+                    file: source,
+                    message: "no feed loaded".to_string(),
+                }))
+            } else {
+                Ok(FeedDiff::new(old, &other, &options))
+            };
+            tx.send(state::feed_diff_mutation(diff, true)).unwrap();
+        });
+    }
+
     fn do_search(&self) {
         let tmdb_client = self.tmdb_client.clone();
         let search_text = self.state.search_text.clone();
@@ -229,6 +570,16 @@ impl LichtApp {
     }
 }
 
+async fn load_routes(
+    cache: &Cache,
+    transit_data: &TransitData,
+    service_date: NaiveDate,
+) -> Vec<state::Route> {
+    let routes = state::load_routes(transit_data, service_date).await;
+    cache.store_routes(service_date, &routes);
+    routes
+}
+
 fn humanize_runtime(runtime: u64) -> String {
     let hours = runtime / 60;
     let minutes = runtime % 60;
@@ -0,0 +1,602 @@
+use std::collections::HashMap;
+
+use chrono::{Days, NaiveDate};
+use egui::Color32;
+
+use crate::ui::{
+    fares::{self, Fare},
+    gtfs::{
+        Accessibility, GtfsTime, Route, SECONDS_PER_DAY, Stop, TRANSFER_NOT_POSSIBLE, TransitData,
+        Trip, nearby_stops,
+    },
+    state::{Station, route_colors},
+};
+
+// Walking transfers are added between stations closer than this, in metres.
+const WALKING_RADIUS: f64 = 400.0;
+pub const WALKING_SPEED: f64 = 1.2;
+const DEFAULT_TRANSFER_TIME: u32 = 120;
+const MAX_ITINERARIES: usize = 3;
+
+struct Connection {
+    trip: usize,
+    from: usize,
+    to: usize,
+    departure: u32,
+    arrival: u32,
+}
+
+struct Footpath {
+    to: usize,
+    duration: u32,
+}
+
+#[derive(Clone, Copy)]
+enum Arrival {
+    Origin,
+    Ride { board: usize, alight: usize },
+    Walk { from: usize, duration: u32 },
+}
+
+pub struct Scan {
+    pub arrivals: Vec<u32>,
+    pointers: Vec<Option<Arrival>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Leg {
+    Ride {
+        route_short_name: String,
+        headsign: String,
+        color: Color32,
+        stations: Vec<Station>,
+        departure: GtfsTime,
+        arrival: GtfsTime,
+        fare: Option<Fare>,
+    },
+    Walk {
+        from: Station,
+        to: Station,
+        departure: GtfsTime,
+        arrival: GtfsTime,
+    },
+    Wait {
+        station: Station,
+        duration: u32,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Itinerary {
+    pub legs: Vec<Leg>,
+    pub departure: GtfsTime,
+    pub arrival: GtfsTime,
+}
+
+impl Itinerary {
+    pub fn transfers(&self) -> usize {
+        self.rides().count().saturating_sub(1)
+    }
+
+    fn rides(&self) -> impl Iterator<Item = &Leg> {
+        self.legs.iter().filter(|l| matches!(l, Leg::Ride { .. }))
+    }
+}
+
+// Connection scan over the stations of a single service day. Platforms are
+// merged into their parent station, trips of the previous service day that run
+// past midnight are included.
+pub struct Planner<'a> {
+    transit_data: &'a TransitData,
+    stations: Vec<&'a Stop>,
+    station_of: HashMap<&'a str, usize>,
+    trips: Vec<(&'a Trip, &'a Route)>,
+    headsigns: HashMap<&'a str, String>,
+    connections: Vec<Connection>,
+    footpaths: Vec<Vec<Footpath>>,
+    transfer_times: Vec<u32>,
+    // Stations where boarding and alighting is allowed, only set for accessible journeys.
+    accessible: Option<Vec<bool>>,
+}
+
+impl<'a> Planner<'a> {
+    #[tracing::instrument(skip(td))]
+    pub fn new(td: &'a TransitData, date: NaiveDate, accessible_only: bool) -> Self {
+        let stations: Vec<&Stop> = td
+            .stops
+            .iter()
+            .filter(|s| {
+                td.station(&s.stop_id)
+                    .is_some_and(|p| p.stop_id == s.stop_id)
+            })
+            .collect();
+        let station_index: HashMap<&str, usize> = stations
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.stop_id.as_str(), i))
+            .collect();
+        let station_of: HashMap<&str, usize> = td
+            .stops
+            .iter()
+            .filter_map(|s| {
+                // Stops on parent cycles have no station to merge into.
+                let station = td.station(&s.stop_id)?;
+                Some((
+                    s.stop_id.as_str(),
+                    *station_index.get(station.stop_id.as_str())?,
+                ))
+            })
+            .collect();
+
+        let mut planner = Planner {
+            transit_data: td,
+            stations,
+            station_of,
+            trips: Vec::new(),
+            headsigns: HashMap::new(),
+            connections: Vec::new(),
+            footpaths: Vec::new(),
+            transfer_times: Vec::new(),
+            accessible: None,
+        };
+
+        if accessible_only {
+            planner.accessible = Some(
+                planner
+                    .stations
+                    .iter()
+                    .map(|s| td.accessibility(&s.stop_id) != Accessibility::NotAccessible)
+                    .collect(),
+            );
+        }
+
+        planner.add_connections(td, date);
+        planner.add_footpaths(td);
+        planner
+    }
+
+    fn add_connections(&mut self, td: &'a TransitData, date: NaiveDate) {
+        let service_days = [
+            (td.service_day(date), 0),
+            (td.service_day(date - Days::new(1)), SECONDS_PER_DAY as i64),
+        ];
+
+        for trip in &td.trips {
+            let Some(route) = td.route(&trip.route_id) else {
+                continue;
+            };
+
+            let stops: Vec<(usize, u32, u32)> = td
+                .trip_stop_times(&trip.trip_id)
+                .iter()
+                .filter_map(|st| {
+                    let station = *self.station_of.get(st.stop_id.as_str())?;
+                    let arrival = st.arrival_time.or(st.departure_time)?;
+                    let departure = st.departure_time.or(st.arrival_time)?;
+                    Some((station, arrival.0, departure.0))
+                })
+                .collect();
+
+            if self.accessible.is_some() && !trip.is_accessible() {
+                continue;
+            }
+
+            for (service_day, day_shift) in &service_days {
+                if !service_day.runs(trip) {
+                    continue;
+                }
+
+                for instance in td.trip_instances(&trip.trip_id) {
+                    let shift = instance.shift - day_shift;
+                    let trip_index = self.trips.len();
+                    let mut added = false;
+
+                    for pair in stops.windows(2) {
+                        let (from, _, departure) = pair[0];
+                        let (to, arrival, _) = pair[1];
+                        let departure = departure as i64 + shift;
+                        let arrival = arrival as i64 + shift;
+                        if from == to || departure < 0 {
+                            continue;
+                        }
+
+                        self.connections.push(Connection {
+                            trip: trip_index,
+                            from,
+                            to,
+                            departure: departure as u32,
+                            arrival: arrival as u32,
+                        });
+                        added = true;
+                    }
+
+                    if added {
+                        self.trips.push((trip, route));
+                    }
+                }
+            }
+        }
+
+        self.connections.sort_by_key(|c| (c.departure, c.arrival));
+        self.headsigns = td.last_stop_names(self.trips.iter().map(|(t, _)| t.trip_id.as_str()));
+    }
+
+    fn add_footpaths(&mut self, td: &TransitData) {
+        self.footpaths = (0..self.stations.len()).map(|_| Vec::new()).collect();
+        self.transfer_times = vec![DEFAULT_TRANSFER_TIME; self.stations.len()];
+
+        for (from, to, distance) in nearby_stops(&self.stations, WALKING_RADIUS) {
+            self.footpaths[from].push(Footpath {
+                to,
+                duration: (distance / WALKING_SPEED).ceil() as u32,
+            });
+        }
+
+        for transfer in &td.transfers {
+            let (Some(from), Some(to)) = (
+                self.station_of.get(transfer.from_stop_id.as_str()).copied(),
+                self.station_of.get(transfer.to_stop_id.as_str()).copied(),
+            ) else {
+                continue;
+            };
+
+            if from == to {
+                if let Some(time) = transfer.min_transfer_time {
+                    self.transfer_times[from] = time;
+                }
+                continue;
+            }
+
+            self.footpaths[from].retain(|f| f.to != to);
+            if transfer.transfer_type == TRANSFER_NOT_POSSIBLE {
+                continue;
+            }
+
+            let duration = transfer.min_transfer_time.unwrap_or_else(|| {
+                self.stations[from]
+                    .distance(self.stations[to])
+                    .map_or(DEFAULT_TRANSFER_TIME, |d| (d / WALKING_SPEED).ceil() as u32)
+            });
+            self.footpaths[from].push(Footpath { to, duration });
+        }
+    }
+
+    pub fn station_index(&self, stop_id: &str) -> Option<usize> {
+        self.station_of.get(stop_id).copied()
+    }
+
+    pub fn station(&self, index: usize) -> Station {
+        Station::from(self.stations[index])
+    }
+
+    // Earliest arrival at every station when leaving `from` at `departure`. The
+    // scan stops early once no connection can improve the arrival at `target`.
+    pub fn scan(&self, from: usize, departure: GtfsTime, target: Option<usize>) -> Scan {
+        let mut arrivals = vec![u32::MAX; self.stations.len()];
+        let mut pointers = vec![None; self.stations.len()];
+        arrivals[from] = departure.0;
+        pointers[from] = Some(Arrival::Origin);
+        for footpath in &self.footpaths[from] {
+            arrivals[footpath.to] = departure.0 + footpath.duration;
+            pointers[footpath.to] = Some(Arrival::Walk {
+                from,
+                duration: footpath.duration,
+            });
+        }
+
+        let mut boarded: Vec<Option<usize>> = vec![None; self.trips.len()];
+        let start = self
+            .connections
+            .partition_point(|c| c.departure < departure.0);
+        for (i, connection) in self.connections.iter().enumerate().skip(start) {
+            if let Some(target) = target
+                && connection.departure >= arrivals[target]
+            {
+                break;
+            }
+
+            let board = match boarded[connection.trip] {
+                Some(board) => board,
+                None => {
+                    if !self.can_board(connection, &arrivals, &pointers) {
+                        continue;
+                    }
+                    boarded[connection.trip] = Some(i);
+                    i
+                }
+            };
+
+            if connection.arrival >= arrivals[connection.to] || !self.can_alight(connection.to) {
+                continue;
+            }
+
+            arrivals[connection.to] = connection.arrival;
+            pointers[connection.to] = Some(Arrival::Ride { board, alight: i });
+            for footpath in &self.footpaths[connection.to] {
+                let arrival = connection.arrival + footpath.duration;
+                if arrival < arrivals[footpath.to] {
+                    arrivals[footpath.to] = arrival;
+                    pointers[footpath.to] = Some(Arrival::Walk {
+                        from: connection.to,
+                        duration: footpath.duration,
+                    });
+                }
+            }
+        }
+
+        Scan { arrivals, pointers }
+    }
+
+    fn can_alight(&self, station: usize) -> bool {
+        self.accessible.as_ref().is_none_or(|a| a[station])
+    }
+
+    // Changing from one vehicle to another at a station takes its minimum transfer time.
+    fn can_board(
+        &self,
+        connection: &Connection,
+        arrivals: &[u32],
+        pointers: &[Option<Arrival>],
+    ) -> bool {
+        let arrival = arrivals[connection.from];
+        if arrival == u32::MAX || !self.can_alight(connection.from) {
+            return false;
+        }
+
+        let transfer_time = match pointers[connection.from] {
+            Some(Arrival::Ride { .. }) => self.transfer_times[connection.from],
+            _ => 0,
+        };
+        arrival + transfer_time <= connection.departure
+    }
+
+    // Up to MAX_ITINERARIES itineraries, each leaving later than the previous one.
+    #[tracing::instrument(skip(self))]
+    pub fn plan(&self, from: &str, to: &str, departure: GtfsTime) -> Vec<Itinerary> {
+        let (Some(from), Some(to)) = (self.station_index(from), self.station_index(to)) else {
+            return Vec::new();
+        };
+
+        if from == to {
+            return Vec::new();
+        }
+
+        let mut itineraries = Vec::new();
+        let mut departure = departure;
+        while itineraries.len() < MAX_ITINERARIES {
+            let scan = self.scan(from, departure, Some(to));
+            let Some(itinerary) = self.itinerary(&scan, to) else {
+                break;
+            };
+
+            let first_ride = itinerary.rides().find_map(|l| match l {
+                Leg::Ride { departure, .. } => Some(*departure),
+                _ => None,
+            });
+            itineraries.push(itinerary);
+
+            match first_ride {
+                Some(first_ride) => departure = GtfsTime(first_ride.0 + 1),
+                None => break,
+            }
+        }
+
+        itineraries
+    }
+
+    fn itinerary(&self, scan: &Scan, to: usize) -> Option<Itinerary> {
+        let mut legs = Vec::new();
+        let mut station = to;
+        loop {
+            match scan.pointers[station]? {
+                Arrival::Origin => break,
+                Arrival::Ride { board, alight } => {
+                    legs.push(self.ride(board, alight));
+                    station = self.connections[board].from;
+                }
+                Arrival::Walk { from, duration } => {
+                    let arrival = scan.arrivals[station];
+                    legs.push(Leg::Walk {
+                        from: self.station(from),
+                        to: self.station(station),
+                        departure: GtfsTime(arrival - duration),
+                        arrival: GtfsTime(arrival),
+                    });
+                    station = from;
+                }
+            }
+        }
+        legs.reverse();
+
+        let departure = GtfsTime(scan.arrivals[station]);
+        let mut with_waits = Vec::new();
+        let mut time = departure;
+        for leg in legs {
+            let (start, end, at) = match &leg {
+                Leg::Ride {
+                    departure,
+                    arrival,
+                    stations,
+                    ..
+                } => (*departure, *arrival, stations[0].clone()),
+                Leg::Walk {
+                    departure,
+                    arrival,
+                    from,
+                    ..
+                } => (*departure, *arrival, from.clone()),
+                Leg::Wait { .. } => continue,
+            };
+
+            // The wait before the first ride is not part of the journey.
+            if start > time && !with_waits.is_empty() {
+                with_waits.push(Leg::Wait {
+                    station: at,
+                    duration: start.0 - time.0,
+                });
+            }
+            with_waits.push(leg);
+            time = end;
+        }
+
+        let departure = match with_waits.first() {
+            Some(Leg::Ride { departure, .. }) | Some(Leg::Walk { departure, .. }) => *departure,
+            _ => departure,
+        };
+
+        Some(Itinerary {
+            legs: with_waits,
+            departure,
+            arrival: GtfsTime(scan.arrivals[to]),
+        })
+    }
+
+    fn ride(&self, board: usize, alight: usize) -> Leg {
+        let first = &self.connections[board];
+        let last = &self.connections[alight];
+        let (trip, route) = self.trips[first.trip];
+
+        let mut stations = vec![self.station(first.from)];
+        stations.extend(
+            self.connections[board..=alight]
+                .iter()
+                .filter(|c| c.trip == first.trip)
+                .map(|c| self.station(c.to)),
+        );
+
+        let headsign = trip
+            .trip_headsign
+            .clone()
+            .or_else(|| self.headsigns.get(trip.trip_id.as_str()).cloned())
+            .unwrap_or_default();
+
+        let fare = fares::fares(
+            self.transit_data,
+            &route.route_id,
+            &self.stations[first.from].stop_id,
+            &self.stations[last.to].stop_id,
+        )
+        .into_iter()
+        .next();
+
+        Leg::Ride {
+            route_short_name: route.route_short_name.clone(),
+            headsign,
+            color: route_colors(route).0,
+            stations,
+            departure: GtfsTime(first.departure),
+            arrival: GtfsTime(last.arrival),
+            fare,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ui::gtfs::tests::read;
+
+    // B and C are 111 m apart, D can also be reached from B by a trip that
+    // leaves before the transfer time at B is over.
+    pub(crate) fn transfer_feed() -> TransitData {
+        let mut td = TransitData::default();
+        td.stops.extend(
+            read(
+                "stop_id,stop_name,stop_lat,stop_lon\n\
+                 a,A,49.0,8.4\n\
+                 b,B,49.01,8.4\n\
+                 c,C,49.011,8.4\n\
+                 d,D,49.03,8.4\n",
+            )
+            .0,
+        );
+        td.routes.extend(
+            read(
+                "route_id,route_short_name,route_type\n\
+                 r1,1,0\n\
+                 r2,2,0\n",
+            )
+            .0,
+        );
+        td.trips.extend(
+            read(
+                "trip_id,route_id,service_id\n\
+                 t1,r1,x\n\
+                 t2,r2,x\n\
+                 t3,r1,x\n",
+            )
+            .0,
+        );
+        td.stop_times.extend(
+            read(
+                "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+                 t1,08:00:00,08:00:00,a,1\n\
+                 t1,08:10:00,08:10:00,b,2\n\
+                 t2,08:15:00,08:15:00,c,1\n\
+                 t2,08:30:00,08:30:00,d,2\n\
+                 t3,08:11:00,08:11:00,b,1\n\
+                 t3,08:20:00,08:20:00,d,2\n",
+            )
+            .0,
+        );
+        td.calendar_dates
+            .extend(read("service_id,date,exception_type\nx,20261019,1\n").0);
+        td.build_index();
+        td
+    }
+
+    #[test]
+    fn journey_with_a_transfer_and_a_footpath() {
+        let td = transfer_feed();
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let planner = Planner::new(&td, date, false);
+        let itineraries = planner.plan("a", "d", GtfsTime::hms(7, 55, 0));
+
+        let itinerary = &itineraries[0];
+        assert_eq!(itinerary.transfers(), 1);
+        assert_eq!(itinerary.departure, GtfsTime::hms(8, 0, 0));
+        assert_eq!(itinerary.arrival, GtfsTime::hms(8, 30, 0));
+        let legs: Vec<String> = itinerary
+            .legs
+            .iter()
+            .map(|leg| match leg {
+                Leg::Ride {
+                    route_short_name,
+                    stations,
+                    ..
+                } => format!("ride {route_short_name} from {}", stations[0].name),
+                Leg::Walk { from, to, .. } => format!("walk from {} to {}", from.name, to.name),
+                Leg::Wait { station, .. } => format!("wait at {}", station.name),
+            })
+            .collect();
+        assert_eq!(
+            legs,
+            [
+                "ride 1 from A",
+                "walk from B to C",
+                "wait at C",
+                "ride 2 from C"
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_on_parent_cycles_are_skipped() {
+        let mut td = TransitData::default();
+        td.stops.extend(
+            read(
+                "stop_id,stop_name,stop_lat,stop_lon,parent_station\n\
+                 a,A,49.0,8.4,b\n\
+                 b,B,49.0,8.4,c\n\
+                 c,C,49.0,8.4,a\n\
+                 d,D,49.01,8.4,\n",
+            )
+            .0,
+        );
+        td.build_index();
+
+        let planner = Planner::new(&td, NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(), false);
+        assert!(planner.station_index("a").is_none());
+        assert!(planner.station_index("d").is_some());
+    }
+}
@@ -0,0 +1,403 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use bytes::Bytes;
+use prost::Message;
+use serde::Deserialize;
+
+use crate::ui::gtfs::{GtfsError, TransitData};
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(from = "String")]
+pub enum RealtimeSource {
+    Url(String),
+    File(PathBuf),
+}
+
+impl From<String> for RealtimeSource {
+    fn from(source: String) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            RealtimeSource::Url(source)
+        } else {
+            RealtimeSource::File(source.into())
+        }
+    }
+}
+
+impl RealtimeSource {
+    #[tracing::instrument]
+    pub async fn fetch(&self) -> Result<proto::FeedMessage, GtfsError> {
+        let bytes = match self {
+            RealtimeSource::Url(url) => download(url).await.map_err(|err| GtfsError::Download {
+                url: url.clone(),
+                message: err.to_string(),
+            })?,
+            RealtimeSource::File(path) => {
+                Bytes::from(std::fs::read(path).map_err(|err| GtfsError::File {
+                    file: path.display().to_string(),
+                    message: err.to_string(),
+                })?)
+            }
+        };
+
+        proto::FeedMessage::decode(bytes).map_err(|err| GtfsError::File {
+            file: self.to_string(),
+            message: err.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for RealtimeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RealtimeSource::Url(url) => write!(f, "{url}"),
+            RealtimeSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+// Prefixes the IDs in a feed with the namespace of the static feed it belongs to.
+pub fn namespace(feed: &mut proto::FeedMessage, namespace: &str) {
+    let prefix = |id: &mut Option<String>| {
+        if let Some(id) = id {
+            *id = format!("{namespace}:{id}");
+        }
+    };
+    let prefix_trip = |trip: &mut proto::TripDescriptor| {
+        prefix(&mut trip.trip_id);
+        prefix(&mut trip.route_id);
+    };
+
+    for entity in &mut feed.entity {
+        if let Some(trip_update) = &mut entity.trip_update {
+            prefix_trip(&mut trip_update.trip);
+            for update in &mut trip_update.stop_time_update {
+                prefix(&mut update.stop_id);
+            }
+        }
+
+        if let Some(trip) = entity.vehicle.as_mut().and_then(|v| v.trip.as_mut()) {
+            prefix_trip(trip);
+        }
+    }
+}
+
+async fn download(url: &str) -> Result<Bytes, reqwest::Error> {
+    reqwest::get(url).await?.error_for_status()?.bytes().await
+}
+
+#[derive(Clone, Debug)]
+pub struct Vehicle {
+    pub route_id: String,
+    pub label: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Clone, Debug)]
+struct StopDelay {
+    stop_sequence: Option<u32>,
+    stop_ids: Vec<String>,
+    delay: i32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Realtime {
+    pub timestamp: u64,
+    pub vehicles: Vec<Vehicle>,
+    delays: HashMap<String, Vec<StopDelay>>,
+    pub unmatched_trips: usize,
+}
+
+impl Realtime {
+    // Only entities whose trip, or for vehicles route, is part of the static
+    // schedule are kept.
+    pub fn new(feed: &proto::FeedMessage, td: &TransitData) -> Self {
+        let mut realtime = Realtime {
+            timestamp: feed.header.as_ref().and_then(|h| h.timestamp).unwrap_or(0),
+            ..Default::default()
+        };
+
+        for entity in &feed.entity {
+            if let Some(vehicle) = &entity.vehicle {
+                realtime.add_vehicle(vehicle, td);
+            }
+
+            if let Some(trip_update) = &entity.trip_update {
+                realtime.add_trip_update(trip_update, td);
+            }
+        }
+
+        realtime
+    }
+
+    fn add_vehicle(&mut self, vehicle: &proto::VehiclePosition, td: &TransitData) {
+        let Some(position) = &vehicle.position else {
+            return;
+        };
+
+        // Vehicles that only name their route are shown on it as well.
+        let descriptor = vehicle.trip.as_ref();
+        let route_id = descriptor
+            .and_then(|t| t.trip_id.as_deref())
+            .and_then(|id| td.trip(id))
+            .map(|t| t.route_id.as_str())
+            .or_else(|| descriptor.and_then(|t| t.route_id.as_deref()));
+        let Some(route) = route_id.and_then(|id| td.route(id)) else {
+            self.unmatched_trips += 1;
+            return;
+        };
+
+        let label = vehicle
+            .vehicle
+            .as_ref()
+            .and_then(|v| v.label.clone().or(v.id.clone()))
+            .unwrap_or_default();
+
+        self.vehicles.push(Vehicle {
+            route_id: route.route_id.clone(),
+            label,
+            lat: position.latitude as f64,
+            lon: position.longitude as f64,
+        });
+    }
+
+    fn add_trip_update(&mut self, trip_update: &proto::TripUpdate, td: &TransitData) {
+        let Some(trip) = trip_update
+            .trip
+            .trip_id
+            .as_deref()
+            .and_then(|id| td.trip(id))
+        else {
+            self.unmatched_trips += 1;
+            return;
+        };
+
+        let mut delays = Vec::new();
+        for update in &trip_update.stop_time_update {
+            let event = update.departure.as_ref().or(update.arrival.as_ref());
+            let Some(delay) = event.and_then(|e| e.delay) else {
+                continue;
+            };
+
+            // Updates can reference platforms, so the whole station is matched.
+            let stop_ids = update
+                .stop_id
+                .as_deref()
+                .and_then(|id| td.station(id))
+                .map(|s| td.stop_ids_at(&s.stop_id))
+                .into_iter()
+                .flatten()
+                .map(|id| id.to_string())
+                .collect();
+
+            delays.push(StopDelay {
+                stop_sequence: update.stop_sequence,
+                stop_ids,
+                delay,
+            });
+        }
+
+        if delays.is_empty()
+            && let Some(delay) = trip_update.delay
+        {
+            delays.push(StopDelay {
+                stop_sequence: Some(0),
+                stop_ids: Vec::new(),
+                delay,
+            });
+        }
+
+        self.delays.insert(trip.trip_id.clone(), delays);
+    }
+
+    // Delay in seconds at the given stop, propagated from the last update before it.
+    pub fn delay(&self, trip_id: &str, stop_id: &str, stop_sequence: u32) -> Option<i32> {
+        let delays = self.delays.get(trip_id)?;
+
+        if let Some(delay) = delays.iter().find(|d| {
+            d.stop_sequence == Some(stop_sequence) || d.stop_ids.iter().any(|id| id == stop_id)
+        }) {
+            return Some(delay.delay);
+        }
+
+        delays
+            .iter()
+            .filter(|d| d.stop_sequence.is_some_and(|s| s <= stop_sequence))
+            .max_by_key(|d| d.stop_sequence)
+            .map(|d| d.delay)
+    }
+
+    pub fn trip_update_count(&self) -> usize {
+        self.delays.len()
+    }
+}
+
+// Hand written subset of the GTFS-Realtime schema
+// https://gtfs.org/documentation/realtime/proto/
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedMessage {
+        #[prost(message, optional, tag = "1")]
+        pub header: Option<FeedHeader>,
+        #[prost(message, repeated, tag = "2")]
+        pub entity: Vec<FeedEntity>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedHeader {
+        #[prost(string, optional, tag = "1")]
+        pub gtfs_realtime_version: Option<String>,
+        #[prost(uint64, optional, tag = "3")]
+        pub timestamp: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedEntity {
+        #[prost(string, optional, tag = "1")]
+        pub id: Option<String>,
+        #[prost(message, optional, tag = "3")]
+        pub trip_update: Option<TripUpdate>,
+        #[prost(message, optional, tag = "4")]
+        pub vehicle: Option<VehiclePosition>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TripUpdate {
+        #[prost(message, required, tag = "1")]
+        pub trip: TripDescriptor,
+        #[prost(message, repeated, tag = "2")]
+        pub stop_time_update: Vec<StopTimeUpdate>,
+        #[prost(int32, optional, tag = "5")]
+        pub delay: Option<i32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StopTimeUpdate {
+        #[prost(uint32, optional, tag = "1")]
+        pub stop_sequence: Option<u32>,
+        #[prost(message, optional, tag = "2")]
+        pub arrival: Option<StopTimeEvent>,
+        #[prost(message, optional, tag = "3")]
+        pub departure: Option<StopTimeEvent>,
+        #[prost(string, optional, tag = "4")]
+        pub stop_id: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StopTimeEvent {
+        #[prost(int32, optional, tag = "1")]
+        pub delay: Option<i32>,
+        #[prost(int64, optional, tag = "2")]
+        pub time: Option<i64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TripDescriptor {
+        #[prost(string, optional, tag = "1")]
+        pub trip_id: Option<String>,
+        #[prost(string, optional, tag = "5")]
+        pub route_id: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VehiclePosition {
+        #[prost(message, optional, tag = "1")]
+        pub trip: Option<TripDescriptor>,
+        #[prost(message, optional, tag = "2")]
+        pub position: Option<Position>,
+        #[prost(message, optional, tag = "8")]
+        pub vehicle: Option<VehicleDescriptor>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Position {
+        #[prost(float, required, tag = "1")]
+        pub latitude: f32,
+        #[prost(float, required, tag = "2")]
+        pub longitude: f32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VehicleDescriptor {
+        #[prost(string, optional, tag = "1")]
+        pub id: Option<String>,
+        #[prost(string, optional, tag = "2")]
+        pub label: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::gtfs::{FeedConfig, FeedSource};
+
+    fn sample() -> TransitData {
+        let sources = [FeedConfig {
+            source: FeedSource::Directory("feeds/sample".into()),
+            namespace: None,
+        }];
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(TransitData::load(&sources))
+    }
+
+    #[test]
+    fn recorded_snapshot_is_matched() {
+        let td = sample();
+        let bytes = std::fs::read("feeds/sample/realtime.pb").unwrap();
+        let feed = proto::FeedMessage::decode(bytes.as_slice()).unwrap();
+        let realtime = Realtime::new(&feed, &td);
+
+        assert_eq!(realtime.timestamp, 1798200000);
+        assert_eq!(realtime.unmatched_trips, 1);
+        let vehicles: Vec<(&str, &str)> = realtime
+            .vehicles
+            .iter()
+            .map(|v| (v.label.as_str(), v.route_id.as_str()))
+            .collect();
+        assert_eq!(
+            vehicles,
+            [("Wagen 401", "2"), ("Wagen 402", "2"), ("Wagen 880", "S1")]
+        );
+        let vehicle = &realtime.vehicles[0];
+        assert!((vehicle.lat - 49.005).abs() < 1e-5 && (vehicle.lon - 8.44).abs() < 1e-5);
+
+        assert_eq!(realtime.trip_update_count(), 2);
+        assert_eq!(realtime.delay("2-0-weekday-40", "de:8212:2", 2), Some(180));
+        // Later stops keep the delay of the last update before them.
+        assert_eq!(realtime.delay("2-0-weekday-40", "de:8212:4", 4), Some(180));
+        assert_eq!(realtime.delay("2-0-weekday-40", "de:8212:1", 1), None);
+        assert_eq!(realtime.delay("2-1-weekday-40", "de:8212:3:2", 2), Some(60));
+    }
+
+    #[test]
+    fn vehicles_without_trip_are_matched_by_route() {
+        let td = sample();
+        let feed = proto::FeedMessage {
+            header: None,
+            entity: vec![proto::FeedEntity {
+                id: Some("403".to_string()),
+                trip_update: None,
+                vehicle: Some(proto::VehiclePosition {
+                    trip: Some(proto::TripDescriptor {
+                        trip_id: None,
+                        route_id: Some("S1".to_string()),
+                    }),
+                    position: Some(proto::Position {
+                        latitude: 49.0,
+                        longitude: 8.4,
+                    }),
+                    vehicle: None,
+                }),
+            }],
+        };
+        let realtime = Realtime::new(&feed, &td);
+
+        assert_eq!(realtime.unmatched_trips, 0);
+        assert_eq!(realtime.vehicles.len(), 1);
+        assert_eq!(realtime.vehicles[0].route_id, "S1");
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use crate::ui::gtfs::{self, Stop};
+
+// Nearest stops are looked for up to this many grid cells away, roughly 30km.
+const MAX_RINGS: i64 = 30;
+// Query words of this length or longer may contain one typo.
+const FUZZY_LENGTH: usize = 4;
+
+// Search index over the stations of a feed, by name and by location.
+#[derive(Clone, Default)]
+pub struct StopIndex {
+    entries: Vec<Entry>,
+    grid: HashMap<(i64, i64), Vec<usize>>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    stop: usize,
+    words: Vec<String>,
+}
+
+impl StopIndex {
+    pub fn new(stops: &[Stop], indexed: impl Fn(&Stop) -> bool) -> Self {
+        let mut index = Self::default();
+        for (i, stop) in stops.iter().enumerate().filter(|(_, s)| indexed(s)) {
+            // Results are shown on the map, so stops without a location are left out.
+            let Some((lat, lon)) = stop.position() else {
+                continue;
+            };
+
+            index
+                .grid
+                .entry(gtfs::grid_cell(lat, lon))
+                .or_default()
+                .push(index.entries.len());
+            index.entries.push(Entry {
+                stop: i,
+                words: words(stop.name()),
+            });
+        }
+
+        index
+    }
+
+    // Indices of the stops matching `query`, best matches first. Every word of the
+    // query has to match the start of a different word of the name, ignoring case
+    // and diacritics, so "Ettl Stadt" finds "Ettlingen Stadt".
+    pub fn search(&self, query: &str, limit: usize) -> Vec<usize> {
+        let query = words(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<(u32, &Entry)> = self
+            .entries
+            .iter()
+            .filter_map(|e| Some((score(&query, &e.words)?, e)))
+            .collect();
+        matches.sort_by(|(a, a_entry), (b, b_entry)| {
+            b.cmp(a)
+                .then(a_entry.words.len().cmp(&b_entry.words.len()))
+                .then(a_entry.words.cmp(&b_entry.words))
+        });
+
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, e)| e.stop)
+            .collect()
+    }
+
+    // Indices of the `count` stops closest to the position, with their distance in metres.
+    pub fn nearest(&self, stops: &[Stop], lat: f64, lon: f64, count: usize) -> Vec<(usize, f64)> {
+        let (cell_lat, cell_lon) = gtfs::grid_cell(lat, lon);
+        // Cells are narrower from east to west, so this is the least distance a
+        // stop one ring further out can have.
+        let cell_size = gtfs::distance(lat, lon, lat, lon + gtfs::GRID_CELL_SIZE);
+
+        let mut found: Vec<(usize, f64)> = Vec::new();
+        for ring in 0..=MAX_RINGS {
+            let cells = (cell_lat - ring..=cell_lat + ring)
+                .flat_map(|lat| (cell_lon - ring..=cell_lon + ring).map(move |lon| (lat, lon)))
+                .filter(|(lat, lon)| {
+                    (lat - cell_lat).abs() == ring || (lon - cell_lon).abs() == ring
+                });
+            for cell in cells {
+                for entry in self.grid.get(&cell).into_iter().flatten() {
+                    let stop = self.entries[*entry].stop;
+                    let (stop_lat, stop_lon) = stops[stop].position().unwrap();
+                    found.push((stop, gtfs::distance(lat, lon, stop_lat, stop_lon)));
+                }
+            }
+
+            found.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+            let complete = found.len() == self.entries.len();
+            let closest = found
+                .get(count.saturating_sub(1))
+                .is_some_and(|(_, d)| *d <= ring as f64 * cell_size);
+            if complete || closest {
+                break;
+            }
+        }
+
+        found.truncate(count);
+        found
+    }
+}
+
+fn words(name: &str) -> Vec<String> {
+    fold(name)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_string())
+        .collect()
+}
+
+// Lower case without diacritics.
+fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.to_lowercase().chars() {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' => folded.push('a'),
+            'ç' | 'ć' | 'č' => folded.push('c'),
+            'ď' => folded.push('d'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => folded.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => folded.push('i'),
+            'ł' => folded.push('l'),
+            'ñ' | 'ń' | 'ň' => folded.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ő' => folded.push('o'),
+            'ř' => folded.push('r'),
+            'ś' | 'š' => folded.push('s'),
+            'ť' => folded.push('t'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ů' | 'ű' => folded.push('u'),
+            'ý' | 'ÿ' => folded.push('y'),
+            'ź' | 'ż' | 'ž' => folded.push('z'),
+            'ß' => folded.push_str("ss"),
+            'æ' => folded.push_str("ae"),
+            'œ' => folded.push_str("oe"),
+            c => folded.push(c),
+        }
+    }
+
+    folded
+}
+
+fn score(query: &[String], words: &[String]) -> Option<u32> {
+    let mut used = vec![false; words.len()];
+    let mut score = 0;
+    for q in query {
+        let (i, word_score) = words
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !used[*i])
+            .filter_map(|(i, w)| Some((i, word_score(q, w)?)))
+            .max_by_key(|(_, s)| *s)?;
+        used[i] = true;
+        score += word_score;
+    }
+
+    // Names starting with the query rank first.
+    if word_score(&query[0], &words[0]).is_some() {
+        score += 1;
+    }
+
+    Some(score)
+}
+
+fn word_score(query: &str, word: &str) -> Option<u32> {
+    if word == query {
+        return Some(4);
+    }
+    if word.starts_with(query) {
+        return Some(3);
+    }
+
+    let query: Vec<char> = query.chars().collect();
+    let word: Vec<char> = word.chars().collect();
+    if query.len() < FUZZY_LENGTH {
+        return None;
+    }
+
+    // The query may be the start of the word with a character missing, added or replaced.
+    (query.len() - 1..=query.len() + 1)
+        .filter(|n| *n <= word.len())
+        .any(|n| edit_distance(&query, &word[..n]) <= 1)
+        .then_some(1)
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::gtfs::tests::read;
+
+    fn stops() -> Vec<Stop> {
+        read(
+            "stop_id,stop_name,stop_lat,stop_lon\n\
+             0,Ettlingen Stadt,48.9415,8.4077\n\
+             1,Ettlinger Tor,49.0051,8.4037\n\
+             2,Marktplatz,49.0094,8.4037\n\
+             3,Mühlburger Tor,49.0110,8.3900\n\
+             4,Ettlingen Bahnhof,48.9405,8.3990\n",
+        )
+        .0
+    }
+
+    #[test]
+    fn search_matches_word_starts() {
+        let stops = stops();
+        let index = StopIndex::new(&stops, |_| true);
+
+        assert_eq!(index.search("Ettl Stadt", 10), [0]);
+        assert_eq!(index.search("muhlb", 10), [3]);
+        assert_eq!(index.search("tor", 10), [1, 3]);
+    }
+
+    #[test]
+    fn nearest_stops_are_sorted_by_distance() {
+        let stops = stops();
+        let index = StopIndex::new(&stops, |_| true);
+
+        let nearest = index.nearest(&stops, 49.009, 8.404, 2);
+        let found: Vec<usize> = nearest.iter().map(|(stop, _)| *stop).collect();
+        assert_eq!(found, [2, 1]);
+        assert!(nearest[0].1 < 100.);
+        assert!(nearest[0].1 < nearest[1].1);
+
+        // Stops further than the neighbouring cells are found as well.
+        let nearest = index.nearest(&stops, 48.94, 8.40, 5);
+        assert_eq!(nearest.len(), 5);
+        assert_eq!(nearest[0].0, 4);
+    }
+}
@@ -0,0 +1,331 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{Days, NaiveDate};
+
+use crate::ui::gtfs::{self, SECONDS_PER_DAY, Stop, TransitData};
+
+// Scheduled trips of a service day, for placing vehicles where the timetable
+// says they should be.
+pub struct Simulation {
+    pub date: NaiveDate,
+    trips: Vec<SimulatedTrip>,
+}
+
+struct SimulatedTrip {
+    route_id: String,
+    headsign: String,
+    path: Arc<Path>,
+    // Arrivals and departures in seconds after midnight of the service date, with
+    // the distance along the path. Trips of the previous day have negative times.
+    events: Vec<(i64, f64)>,
+}
+
+// Line geometry with the distance in metres from the start to each point.
+struct Path {
+    points: Vec<(f64, f64)>,
+    distances: Vec<f64>,
+}
+
+pub struct Vehicle<'a> {
+    pub route_id: &'a str,
+    pub headsign: &'a str,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Simulation {
+    pub fn new(td: &TransitData, date: NaiveDate) -> Self {
+        let shapes = td.shapes_by_id();
+        // Trips with the same shape and stops share their path.
+        let mut paths = HashMap::new();
+
+        let service_days = [
+            (td.service_day(date), 0),
+            (
+                td.service_day(date - Days::new(1)),
+                -(SECONDS_PER_DAY as i64),
+            ),
+        ];
+
+        let mut trips = Vec::new();
+        for (service_day, offset) in service_days {
+            for trip in td.trips.iter().filter(|t| service_day.runs(t)) {
+                let trip_stop_times: Vec<_> = td
+                    .trip_stop_times(&trip.trip_id)
+                    .into_iter()
+                    .filter_map(|st| {
+                        let stop = td.stop(&st.stop_id).filter(|s| s.position().is_some())?;
+                        Some((st, stop))
+                    })
+                    .collect();
+                if trip_stop_times.len() < 2 {
+                    continue;
+                }
+
+                let stop_ids: Vec<&str> = trip_stop_times
+                    .iter()
+                    .map(|(st, _)| st.stop_id.as_str())
+                    .collect();
+                let (path, stop_distances) = paths
+                    .entry((trip.shape_id.as_deref(), stop_ids))
+                    .or_insert_with(|| {
+                        let stops: Vec<&Stop> = trip_stop_times.iter().map(|(_, s)| *s).collect();
+                        match trip.shape_id.as_deref().and_then(|id| shapes.get(id)) {
+                            Some(shape) if shape.len() >= 2 => {
+                                let path = Path::new(
+                                    shape
+                                        .iter()
+                                        .map(|s| (s.shape_pt_lat, s.shape_pt_lon))
+                                        .collect(),
+                                );
+                                let stop_distances = path.locate(&stops);
+                                (Arc::new(path), stop_distances)
+                            }
+                            _ => {
+                                let path = Path::new(
+                                    stops.iter().map(|s| s.position().unwrap()).collect(),
+                                );
+                                let stop_distances = path.distances.clone();
+                                (Arc::new(path), stop_distances)
+                            }
+                        }
+                    });
+
+                let mut events: Vec<(i64, f64)> = Vec::new();
+                for ((stop_time, _), distance) in trip_stop_times.iter().zip(stop_distances.iter())
+                {
+                    for time in [stop_time.arrival_time, stop_time.departure_time]
+                        .into_iter()
+                        .flatten()
+                    {
+                        let time = time.0 as i64 + offset;
+                        if events.last() != Some(&(time, *distance)) {
+                            events.push((time, *distance));
+                        }
+                    }
+                }
+                if events.len() < 2 {
+                    continue;
+                }
+
+                let headsign = trip.trip_headsign.clone().unwrap_or_else(|| {
+                    trip_stop_times[trip_stop_times.len() - 1]
+                        .1
+                        .name()
+                        .to_string()
+                });
+                for instance in td.trip_instances(&trip.trip_id) {
+                    let events: Vec<(i64, f64)> = events
+                        .iter()
+                        .map(|(time, distance)| (time + instance.shift, *distance))
+                        .collect();
+                    // Trips of the previous day that end before midnight never show up.
+                    if events[events.len() - 1].0 < 0 {
+                        continue;
+                    }
+
+                    trips.push(SimulatedTrip {
+                        route_id: trip.route_id.clone(),
+                        headsign: headsign.clone(),
+                        path: path.clone(),
+                        events,
+                    });
+                }
+            }
+        }
+
+        Self { date, trips }
+    }
+
+    // Vehicles of the trips running at `time` seconds after midnight.
+    pub fn vehicles(&self, time: f64) -> impl Iterator<Item = Vehicle<'_>> {
+        self.trips.iter().filter_map(move |trip| {
+            let (lat, lon) = trip.position(time)?;
+            Some(Vehicle {
+                route_id: &trip.route_id,
+                headsign: &trip.headsign,
+                lat,
+                lon,
+            })
+        })
+    }
+}
+
+impl SimulatedTrip {
+    fn position(&self, time: f64) -> Option<(f64, f64)> {
+        let (first, last) = (self.events[0], self.events[self.events.len() - 1]);
+        if time < first.0 as f64 || time > last.0 as f64 {
+            return None;
+        }
+
+        // Between two events the vehicle moves at constant speed, at a stop it waits.
+        let next = self
+            .events
+            .partition_point(|(t, _)| *t as f64 <= time)
+            .min(self.events.len() - 1);
+        let (a, b) = (self.events[next - 1], self.events[next]);
+        let fraction = match b.0 - a.0 {
+            0 => 1.,
+            duration => ((time - a.0 as f64) / duration as f64).clamp(0., 1.),
+        };
+
+        Some(self.path.point_at(a.1 + fraction * (b.1 - a.1)))
+    }
+}
+
+impl Path {
+    fn new(points: Vec<(f64, f64)>) -> Self {
+        let mut distances = vec![0.];
+        for pair in points.windows(2) {
+            let ((lat_a, lon_a), (lat_b, lon_b)) = (pair[0], pair[1]);
+            distances
+                .push(distances[distances.len() - 1] + gtfs::distance(lat_a, lon_a, lat_b, lon_b));
+        }
+
+        Self { points, distances }
+    }
+
+    // Distance along the path of each stop. Stops are matched to the closest
+    // segment not before the segment of the previous stop.
+    fn locate(&self, stops: &[&Stop]) -> Vec<f64> {
+        let mut segment = 0;
+        let mut located = Vec::with_capacity(stops.len());
+        for stop in stops {
+            let (closest, fraction, _) = (segment..self.points.len() - 1)
+                .map(|i| {
+                    let (lat, lon) = stop.position().unwrap();
+                    let (fraction, distance) = self.project(i, lat, lon);
+                    (i, fraction, distance)
+                })
+                .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+                .unwrap();
+            segment = closest;
+            located.push(
+                self.distances[closest]
+                    + fraction * (self.distances[closest + 1] - self.distances[closest]),
+            );
+        }
+
+        located
+    }
+
+    // Position of the point on segment `i` closest to the coordinates, as a
+    // fraction of the segment, and its squared distance in degrees of latitude.
+    fn project(&self, i: usize, lat: f64, lon: f64) -> (f64, f64) {
+        let ((lat_a, lon_a), (lat_b, lon_b)) = (self.points[i], self.points[i + 1]);
+        let scale = lat.to_radians().cos();
+        let (dx, dy) = ((lon_b - lon_a) * scale, lat_b - lat_a);
+        let (px, py) = ((lon - lon_a) * scale, lat - lat_a);
+
+        let length = dx * dx + dy * dy;
+        let fraction = if length > 0. {
+            ((px * dx + py * dy) / length).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let (ex, ey) = (px - fraction * dx, py - fraction * dy);
+
+        (fraction, ex * ex + ey * ey)
+    }
+
+    fn point_at(&self, distance: f64) -> (f64, f64) {
+        let i = self
+            .distances
+            .partition_point(|d| *d <= distance)
+            .clamp(1, self.points.len() - 1);
+        let (start, end) = (self.distances[i - 1], self.distances[i]);
+        let fraction = if end > start {
+            ((distance - start) / (end - start)).clamp(0., 1.)
+        } else {
+            0.
+        };
+
+        let ((lat_a, lon_a), (lat_b, lon_b)) = (self.points[i - 1], self.points[i]);
+        (
+            lat_a + fraction * (lat_b - lat_a),
+            lon_a + fraction * (lon_b - lon_a),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::gtfs::tests::{date, read, small_feed};
+
+    fn assert_close(a: (f64, f64), b: (f64, f64)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn stops_are_located_along_the_path() {
+        let path = Path::new(vec![(49.0, 8.4), (49.01, 8.4), (49.01, 8.42)]);
+        let (stops, _): (Vec<Stop>, _) = read(
+            "stop_id,stop_name,stop_lat,stop_lon\n\
+             a,A,49.005,8.4001\n\
+             b,B,49.0101,8.41\n",
+        );
+        let located = path.locate(&stops.iter().collect::<Vec<_>>());
+
+        let second = path.distances[2] - path.distances[1];
+        assert!((located[0] - path.distances[1] / 2.).abs() < 1.);
+        assert!((located[1] - (path.distances[1] + second / 2.)).abs() < 1.);
+
+        assert_close(path.point_at(located[0]), (49.005, 8.4));
+        assert_close(
+            path.point_at(path.distances[1] + second / 2.),
+            (49.01, 8.41),
+        );
+        assert_close(path.point_at(-1.), (49.0, 8.4));
+        assert_close(path.point_at(path.distances[2] + 1.), (49.01, 8.42));
+    }
+
+    #[test]
+    fn vehicles_move_between_stops() {
+        let mut td = small_feed(
+            "trip_id,route_id,service_id\n\
+             t,r,x\n",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             t,08:00:00,08:00:00,a,1\n\
+             t,08:10:00,08:10:00,b,2\n",
+        );
+        td.build_index();
+        let simulation = Simulation::new(&td, date());
+
+        let vehicles: Vec<_> = simulation.vehicles(8. * 3600. + 150.).collect();
+        assert_eq!(vehicles.len(), 1);
+        assert_close((vehicles[0].lat, vehicles[0].lon), (49.0025, 8.4));
+        assert_eq!(vehicles[0].headsign, "B");
+        assert_eq!(simulation.vehicles(8. * 3600. - 1.).count(), 0);
+        assert_eq!(simulation.vehicles(8. * 3600. + 601.).count(), 0);
+    }
+
+    #[test]
+    fn trips_of_the_previous_day_run_past_midnight() {
+        let mut td = small_feed(
+            "trip_id,route_id,service_id\n\
+             late,r,x\n\
+             evening,r,x\n",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             late,23:50:00,23:50:00,a,1\n\
+             late,24:10:00,24:10:00,b,2\n\
+             evening,22:00:00,22:00:00,a,1\n\
+             evening,22:10:00,22:10:00,b,2\n",
+        );
+        td.build_index();
+        // Service x only runs on the day before.
+        let simulation = Simulation::new(&td, date() + Days::new(1));
+
+        let vehicles: Vec<_> = simulation.vehicles(0.).collect();
+        assert_eq!(vehicles.len(), 1);
+        assert_close((vehicles[0].lat, vehicles[0].lon), (49.005, 8.4));
+        assert_eq!(simulation.vehicles(600.).count(), 1);
+        assert_eq!(simulation.vehicles(601.).count(), 0);
+        // The trips don't run again in the evening of the simulated day.
+        assert_eq!(simulation.vehicles(23. * 3600. + 55. * 60.).count(), 0);
+        assert_eq!(simulation.vehicles(22. * 3600. + 300.).count(), 0);
+    }
+}
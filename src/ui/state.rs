@@ -1,16 +1,35 @@
+use chrono::{Local, NaiveDate, Timelike};
+use egui::Color32;
 use reqwest::{ClientBuilder, Method};
-use serde::Deserialize;
-use tracing::info;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 use walkers::{
     HttpTiles, MapMemory,
     sources::{Mapbox, MapboxStyle},
 };
 
 use crate::ui::{
-    gtfs::TransitData,
+    diff::{self, FeedDiff},
+    export::{self, ExportSummary},
+    fares,
+    gtfs::{
+        self, Accessibility, Departure, GtfsError, GtfsTime, Mode, ServiceDay, Shape, Stop,
+        TransitData, Trip,
+    },
+    isochrone::Isochrone,
+    planner::Itinerary,
+    realtime::{Realtime, proto::FeedMessage},
+    simulation::Simulation,
+    stats::{DayType, RouteStats},
+    timetable::Timetable,
     tmdb::{MovieCastMember, MovieCreditsResponse, MovieDetailsResponse},
 };
-use std::{collections::HashSet, time::Instant};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
 
 #[derive(Clone)]
 pub struct MovieSearch {
@@ -96,12 +115,35 @@ pub struct State {
     pub show_map: bool,
     pub tiles: HttpTiles,
     pub map_memory: MapMemory,
+    pub transit_data: Option<Arc<TransitData>>,
+    pub service_date: NaiveDate,
     pub routes: Vec<Route>,
+    pub route_filter: RouteFilter,
     pub current_route: Option<Route>,
+    pub current_pattern: Option<usize>,
+    pub color_by_zone: bool,
+    pub show_accessibility: bool,
+    pub accessible_only: bool,
+    pub departure_board: Option<DepartureBoard>,
+    pub realtime: Option<Realtime>,
+    pub realtime_error: Option<GtfsError>,
+    pub journey_planner: JourneyPlanner,
+    pub visible_area: Option<export::BoundingBox>,
+    pub export: Export,
+    pub feed_diff: FeedDiffPanel,
+    pub stop_search: String,
+    pub stop_search_results: Vec<Station>,
+    pub nearest_stops: Option<NearestStops>,
+    pub timetable: Option<Timetable>,
+    pub route_stats: Option<RouteStats>,
+    pub route_stats_day: DayType,
+    pub isochrone: IsochronePanel,
+    pub simulation: SimulationPanel,
 }
 
 impl State {
-    pub fn new(token: String, ctx: egui::Context) -> Self {
+    pub fn new(token: String, route_filter: RouteFilter, ctx: egui::Context) -> Self {
+        let now = Local::now();
         Self {
             search_text: String::new(),
             movie_searches: Vec::new(),
@@ -117,8 +159,173 @@ impl State {
                 ctx,
             ),
             map_memory: MapMemory::default(),
+            transit_data: None,
+            service_date: now.date_naive(),
             routes: Vec::new(),
+            route_filter,
             current_route: None,
+            current_pattern: None,
+            color_by_zone: false,
+            show_accessibility: false,
+            accessible_only: false,
+            departure_board: None,
+            realtime: None,
+            realtime_error: None,
+            journey_planner: JourneyPlanner {
+                hour: now.hour(),
+                minute: now.minute(),
+                ..Default::default()
+            },
+            visible_area: None,
+            export: Export {
+                path: "export.zip".to_string(),
+                days: 7,
+                ..Default::default()
+            },
+            feed_diff: FeedDiffPanel {
+                moved_distance: diff::DEFAULT_MOVED_DISTANCE,
+                days: 7,
+                path: "feed_diff".to_string(),
+                ..Default::default()
+            },
+            stop_search: String::new(),
+            stop_search_results: Vec::new(),
+            nearest_stops: None,
+            timetable: None,
+            route_stats: None,
+            route_stats_day: DayType::Weekday,
+            isochrone: IsochronePanel {
+                hour: now.hour(),
+                minute: now.minute(),
+                ..Default::default()
+            },
+            simulation: SimulationPanel {
+                time: now.num_seconds_from_midnight() as f64,
+                speed: 1.,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct JourneyPlanner {
+    pub open: bool,
+    pub from: Option<Station>,
+    pub to: Option<Station>,
+    pub hour: u32,
+    pub minute: u32,
+    pub searching: bool,
+    pub itineraries: Option<Vec<Itinerary>>,
+    pub selected: usize,
+}
+
+impl JourneyPlanner {
+    pub fn departure(&self) -> GtfsTime {
+        GtfsTime::hms(self.hour, self.minute, 0)
+    }
+}
+
+#[derive(Default)]
+pub struct Export {
+    pub path: String,
+    pub selected_route_only: bool,
+    pub visible_area_only: bool,
+    pub days: u64,
+    pub running: bool,
+    pub result: Option<Result<ExportSummary, GtfsError>>,
+}
+
+#[derive(Default)]
+pub struct IsochronePanel {
+    pub open: bool,
+    pub origin: Option<Station>,
+    pub hour: u32,
+    pub minute: u32,
+    pub computing: bool,
+    pub result: Option<Isochrone>,
+}
+
+impl IsochronePanel {
+    pub fn departure(&self) -> GtfsTime {
+        GtfsTime::hms(self.hour, self.minute, 0)
+    }
+}
+
+#[derive(Default)]
+pub struct SimulationPanel {
+    pub open: bool,
+    pub running: bool,
+    // Simulated seconds per second.
+    pub speed: f64,
+    // Seconds after midnight of the service date.
+    pub time: f64,
+    pub loading: bool,
+    pub simulation: Option<Arc<Simulation>>,
+}
+
+// Stations around the point the map was right-clicked at.
+pub struct NearestStops {
+    pub position: egui::Pos2,
+    pub stations: Vec<(Station, f64)>,
+}
+
+#[derive(Default)]
+pub struct FeedDiffPanel {
+    pub open: bool,
+    // Feed compared against the loaded one.
+    pub source: String,
+    // The configured feed it is a version of.
+    pub feed: usize,
+    pub moved_distance: f64,
+    pub days: u64,
+    pub running: bool,
+    pub diff: Option<Result<FeedDiff, GtfsError>>,
+    pub path: String,
+    pub saved: Option<Result<PathBuf, String>>,
+}
+
+pub struct RouteFilter {
+    pub agencies: Option<HashSet<String>>,
+    pub modes: HashSet<Mode>,
+}
+
+impl RouteFilter {
+    pub fn new(agencies: Option<Vec<String>>, modes: Vec<Mode>) -> Self {
+        Self {
+            agencies: agencies.map(|a| a.into_iter().collect()),
+            modes: modes.into_iter().collect(),
+        }
+    }
+
+    pub fn matches(&self, route: &Route) -> bool {
+        self.shows_agency(&route.agency_id) && self.modes.contains(&route.mode)
+    }
+
+    pub fn shows_agency(&self, agency_id: &str) -> bool {
+        match &self.agencies {
+            Some(agencies) => agencies.contains(agency_id),
+            None => true,
+        }
+    }
+
+    pub fn set_agency(&mut self, agency_id: &str, show: bool, all_agency_ids: &[&str]) {
+        let agencies = self
+            .agencies
+            .get_or_insert_with(|| all_agency_ids.iter().map(|a| a.to_string()).collect());
+
+        if show {
+            agencies.insert(agency_id.to_string());
+        } else {
+            agencies.remove(agency_id);
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: Mode, show: bool) {
+        if show {
+            self.modes.insert(mode);
+        } else {
+            self.modes.remove(&mode);
         }
     }
 }
@@ -129,10 +336,84 @@ pub fn movie_search_mutation(movie_search: MovieSearch) -> StateMutation {
     Box::new(move |state: &mut State| state.movie_searches.push(movie_search.clone()))
 }
 
+pub fn transit_data_mutation(transit_data: Arc<TransitData>) -> StateMutation {
+    Box::new(move |state: &mut State| state.transit_data = Some(transit_data.clone()))
+}
+
 pub fn routes_mutation(routes: Vec<Route>) -> StateMutation {
     Box::new(move |state: &mut State| {
+        let current_id = state.current_route.as_ref().map(|r| r.route_id.clone());
         state.routes = routes.clone();
-        state.current_route = routes.first().cloned();
+        state.current_route = routes
+            .iter()
+            .find(|r| Some(&r.route_id) == current_id.as_ref())
+            .or_else(|| routes.iter().find(|r| state.route_filter.matches(r)))
+            .cloned();
+        if state.current_route.as_ref().map(|r| &r.route_id) != current_id.as_ref() {
+            state.current_pattern = None;
+        }
+    })
+}
+
+pub fn departure_board_mutation(departure_board: DepartureBoard) -> StateMutation {
+    Box::new(move |state: &mut State| state.departure_board = Some(departure_board.clone()))
+}
+
+pub fn itineraries_mutation(itineraries: Vec<Itinerary>) -> StateMutation {
+    Box::new(move |state: &mut State| {
+        state.journey_planner.itineraries = Some(itineraries.clone());
+        state.journey_planner.selected = 0;
+        state.journey_planner.searching = false;
+    })
+}
+
+pub fn export_mutation(result: Result<ExportSummary, GtfsError>) -> StateMutation {
+    Box::new(move |state: &mut State| {
+        state.export.result = Some(result.clone());
+        state.export.running = false;
+    })
+}
+
+pub fn feed_diff_mutation(diff: Result<FeedDiff, GtfsError>, open: bool) -> StateMutation {
+    Box::new(move |state: &mut State| {
+        state.feed_diff.diff = Some(diff.clone());
+        state.feed_diff.running = false;
+        state.feed_diff.saved = None;
+        state.feed_diff.open |= open;
+    })
+}
+
+pub fn timetable_mutation(timetable: Timetable) -> StateMutation {
+    Box::new(move |state: &mut State| state.timetable = Some(timetable.clone()))
+}
+
+pub fn route_stats_mutation(stats: RouteStats) -> StateMutation {
+    Box::new(move |state: &mut State| state.route_stats = Some(stats.clone()))
+}
+
+pub fn simulation_mutation(simulation: Arc<Simulation>) -> StateMutation {
+    Box::new(move |state: &mut State| {
+        state.simulation.simulation = Some(simulation.clone());
+        state.simulation.loading = false;
+    })
+}
+
+pub fn isochrone_mutation(isochrone: Isochrone) -> StateMutation {
+    Box::new(move |state: &mut State| {
+        state.isochrone.result = Some(isochrone.clone());
+        state.isochrone.computing = false;
+    })
+}
+
+pub fn realtime_mutation(feed: Result<FeedMessage, GtfsError>) -> StateMutation {
+    Box::new(move |state: &mut State| match &feed {
+        Ok(feed) => {
+            if let Some(transit_data) = &state.transit_data {
+                state.realtime = Some(Realtime::new(feed, transit_data));
+                state.realtime_error = None;
+            }
+        }
+        Err(err) => state.realtime_error = Some(err.clone()),
     })
 }
 
@@ -147,103 +428,371 @@ fn build_poster_url(poster_path: Option<String>) -> String {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Station {
+    pub stop_id: String,
     pub name: String,
     pub lon: f64,
     pub lat: f64,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl From<&Stop> for Station {
+    fn from(stop: &Stop) -> Self {
+        // Stations and platforms always have a location, only generic nodes may not.
+        let (lat, lon) = stop.position().unwrap_or_default();
+        Self {
+            stop_id: stop.stop_id.clone(),
+            name: stop.name().to_string(),
+            lon,
+            lat,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DepartureBoard {
+    pub station: Station,
+    pub zone: Option<String>,
+    pub accessibility: Accessibility,
+    pub levels: Vec<String>,
+    pub departures: Vec<Departure>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub stations: Vec<Station>,
+    pub direction_id: Option<u8>,
+    pub headsign: String,
+    pub trip_count: usize,
+    pub trip_ids: Vec<String>,
+    pub elements: Vec<Element>,
+}
+
+impl Pattern {
+    fn new(
+        index: &RouteIndex,
+        direction_id: Option<u8>,
+        headsign: Option<&str>,
+        sequence: Vec<&str>,
+        trips: &[&Trip],
+    ) -> Self {
+        let mut stations: Vec<Station> = sequence
+            .into_iter()
+            .filter_map(|stop_id| index.transit_data.station(stop_id))
+            .filter(|s| s.position().is_some())
+            .map(Station::from)
+            .collect();
+        stations.dedup_by(|a, b| a.stop_id == b.stop_id);
+
+        let headsign = headsign
+            .map(|h| h.to_string())
+            .or_else(|| stations.last().map(|s| s.name.clone()))
+            .unwrap_or_default();
+
+        let trip_count = trips
+            .iter()
+            .map(|t| index.transit_data.trip_instances(&t.trip_id).len())
+            .sum();
+
+        let shape_ids: BTreeSet<&str> =
+            trips.iter().filter_map(|t| t.shape_id.as_deref()).collect();
+        let elements = shape_ids
+            .into_iter()
+            .filter_map(|id| index.shapes.get(id))
+            .map(|points| Element::from_shape(points))
+            .collect();
+
+        Pattern {
+            stations,
+            direction_id,
+            headsign,
+            trip_count,
+            trip_ids: trips.iter().map(|t| t.trip_id.clone()).collect(),
+            elements,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Route {
     pub stations: Vec<Station>,
+    pub patterns: Vec<Pattern>,
+    pub route_id: String,
     pub name: String,
-    pub elements: Vec<Element>,
+    // The name, with the agency added when routes of several agencies share it.
+    pub label: String,
+    pub agency_id: String,
+    pub mode: Mode,
+    pub color: Color32,
+    pub text_color: Color32,
+    pub trip_count: usize,
+    pub headway: Option<u32>,
+    // Looked up once per station, by stop ID, instead of on every frame.
+    pub markers: HashMap<String, StationMarker>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StationMarker {
+    pub zone: Option<String>,
+    pub step_free: bool,
+}
+
+impl StationMarker {
+    fn new(td: &TransitData, station_id: &str) -> Self {
+        Self {
+            zone: fares::zone(td, station_id),
+            step_free: td.accessibility(station_id) == Accessibility::StepFree,
+        }
+    }
+}
+
+const ROUTE_PALETTE: [Color32; 8] = [
+    Color32::from_rgb(0xe6, 0x19, 0x4b),
+    Color32::from_rgb(0x3c, 0xb4, 0x4b),
+    Color32::from_rgb(0x43, 0x63, 0xd8),
+    Color32::from_rgb(0xf5, 0x82, 0x31),
+    Color32::from_rgb(0x91, 0x1e, 0xb4),
+    Color32::from_rgb(0x42, 0xd4, 0xf4),
+    Color32::from_rgb(0xf0, 0x32, 0xe6),
+    Color32::from_rgb(0xbf, 0xef, 0x45),
+];
+
+pub fn route_colors(route: &gtfs::Route) -> (Color32, Color32) {
+    let color =
+        parse_color(route.route_color.as_deref()).unwrap_or_else(|| palette_color(&route.route_id));
+    let text_color = parse_color(route.route_text_color.as_deref()).unwrap_or(text_color_on(color));
+
+    (color, text_color)
+}
+
+// A stable palette colour for IDs without a colour of their own.
+pub fn palette_color(id: &str) -> Color32 {
+    let hash = id
+        .bytes()
+        .fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+    ROUTE_PALETTE[hash % ROUTE_PALETTE.len()]
+}
+
+pub fn text_color_on(color: Color32) -> Color32 {
+    if color.intensity() > 0.5 {
+        Color32::BLACK
+    } else {
+        Color32::WHITE
+    }
+}
+
+fn parse_color(hex: Option<&str>) -> Option<Color32> {
+    let hex = hex?.trim();
+    if hex.is_empty() {
+        return None;
+    }
+
+    Color32::from_hex(&format!("#{hex}")).ok()
+}
+
+// Direction, headsign and stop sequence of a trip.
+type PatternKey<'a> = (Option<u8>, Option<&'a str>, Vec<&'a str>);
+
+struct RouteIndex<'a> {
+    transit_data: &'a TransitData,
+    service_day: ServiceDay<'a>,
+    shapes: HashMap<&'a str, Vec<&'a Shape>>,
+    trips: HashMap<&'a str, Vec<&'a Trip>>,
+}
+
+impl<'a> RouteIndex<'a> {
+    fn new(td: &'a TransitData, date: NaiveDate) -> Self {
+        let mut trips: HashMap<&str, Vec<&Trip>> = HashMap::new();
+        for trip in &td.trips {
+            trips.entry(&trip.route_id).or_default().push(trip);
+        }
+
+        Self {
+            transit_data: td,
+            service_day: td.service_day(date),
+            shapes: td.shapes_by_id(),
+            trips,
+        }
+    }
 }
 
 impl Route {
-    fn new(td: &TransitData, route_id: &str, name: String) -> Self {
-        let trip_ids: HashSet<&String> = td
+    // The selected pattern, or all of them.
+    pub fn patterns(&self, selected: Option<usize>) -> &[Pattern] {
+        match selected {
+            Some(i) if i < self.patterns.len() => &self.patterns[i..=i],
+            _ => &self.patterns,
+        }
+    }
+
+    fn new(index: &RouteIndex, route: &gtfs::Route) -> Self {
+        let trips: Vec<&Trip> = index
             .trips
-            .iter()
-            .filter(|t| t.route_id == route_id)
-            .map(|t| &t.trip_id)
+            .get(route.route_id.as_str())
+            .into_iter()
+            .flatten()
+            .filter(|t| index.service_day.runs(t))
+            .copied()
             .collect();
 
-        let stop_ids: HashSet<&String> = td
-            .stop_times
-            .iter()
-            .filter(|st| trip_ids.contains(&st.trip_id))
-            .map(|st| &st.stop_id)
+        // Trips are grouped into patterns by direction, headsign and stop sequence,
+        // so that directions and branches are kept apart.
+        let mut groups: HashMap<PatternKey, Vec<&Trip>> = HashMap::new();
+        for trip in &trips {
+            let stop_times = index.transit_data.trip_stop_times(&trip.trip_id);
+            if stop_times.is_empty() {
+                continue;
+            }
+
+            let sequence = stop_times.iter().map(|st| st.stop_id.as_str()).collect();
+            groups
+                .entry((trip.direction_id, trip.trip_headsign.as_deref(), sequence))
+                .or_default()
+                .push(trip);
+        }
+
+        let mut patterns: Vec<Pattern> = groups
+            .into_iter()
+            .map(|((direction_id, headsign, sequence), trips)| {
+                Pattern::new(index, direction_id, headsign, sequence, &trips)
+            })
             .collect();
+        // Patterns with more trips come first.
+        patterns.sort_by(|a, b| {
+            b.trip_count
+                .cmp(&a.trip_count)
+                .then(a.direction_id.cmp(&b.direction_id))
+                .then(a.headsign.cmp(&b.headsign))
+        });
+
+        let trip_count = patterns.iter().map(|p| p.trip_count).sum();
+        let headway = trips
+            .iter()
+            .flat_map(|t| index.transit_data.trip_instances(&t.trip_id))
+            .filter_map(|i| i.headway)
+            .min();
+
+        let mut stations: Vec<Station> = Vec::new();
+        for station in patterns.iter().flat_map(|p| &p.stations) {
+            if !stations.iter().any(|s| s.stop_id == station.stop_id) {
+                stations.push(station.clone());
+            }
+        }
 
-        let stations: Vec<Station> = td
-            .stops
+        let markers = stations
             .iter()
-            .filter(|s| stop_ids.contains(&s.stop_id))
-            .map(|s| Station {
-                name: s.stop_name.clone(),
-                lon: s.stop_lon,
-                lat: s.stop_lat,
+            .map(|s| {
+                let marker = StationMarker::new(index.transit_data, &s.stop_id);
+                (s.stop_id.clone(), marker)
             })
             .collect();
 
+        let name = route.route_short_name.clone();
+        let (color, text_color) = route_colors(route);
         info!(name);
         Route {
             stations,
+            patterns,
+            route_id: route.route_id.clone(),
+            label: name.clone(),
             name,
-            elements: vec![],
+            agency_id: route.agency_id.clone(),
+            mode: route.mode(),
+            color,
+            text_color,
+            trip_count,
+            headway,
+            markers,
         }
     }
 }
 
 #[tracing::instrument(skip(transit_data))]
-pub async fn load_routes(transit_data: &TransitData) -> Vec<Route> {
+pub async fn load_routes(transit_data: &TransitData, date: NaiveDate) -> Vec<Route> {
+    let index = RouteIndex::new(transit_data, date);
+
     let mut routes = Vec::new();
     for r in &transit_data.routes {
-        routes.push(Route::new(
-            transit_data,
-            &r.route_id,
-            r.route_short_name.clone(),
-        ));
+        let route = Route::new(&index, r);
+
+        if !route.stations.is_empty() {
+            routes.push(route);
+        }
     }
 
+    routes.sort_by(|a, b| a.name.cmp(&b.name));
+    label_shared_names(transit_data, &mut routes);
     calculate_geopoints(&mut routes).await;
 
     routes
 }
 
+// Routes of different agencies can share a name once several feeds are loaded.
+fn label_shared_names(transit_data: &TransitData, routes: &mut [Route]) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for route in routes.iter() {
+        *counts.entry(route.name.clone()).or_default() += 1;
+    }
+
+    for route in routes.iter_mut().filter(|r| counts[&r.name] > 1) {
+        if let Some(agency) = transit_data
+            .agencies
+            .iter()
+            .find(|a| a.agency_id == route.agency_id)
+        {
+            route.label = format!("{} ({})", route.name, agency.agency_name);
+        }
+    }
+}
+
 #[tracing::instrument(skip(routes))]
 async fn calculate_geopoints(routes: &mut [Route]) {
-    let bounding_box = bounding_box(routes);
-    let ways = load_ways(&bounding_box).await;
+    let routes: Vec<&mut Route> = routes
+        .iter_mut()
+        .filter(|r| r.mode.is_rail() && r.patterns.iter().any(|p| p.elements.is_empty()))
+        .collect();
+    if routes.is_empty() {
+        return;
+    }
+
+    let bounding_box = bounding_box(&routes);
+    let ways = match load_ways(&bounding_box).await {
+        Ok(ways) => ways,
+        Err(err) => {
+            warn!("failed to load railway ways: {err}");
+            return;
+        }
+    };
     info!("railway elements: {}", ways.elements.len());
 
-    for route in routes {
-        let elements = calculate_elements(route, &ways).await;
+    for pattern in routes
+        .into_iter()
+        .flat_map(|r| &mut r.patterns)
+        .filter(|p| p.elements.is_empty())
+    {
+        let elements = calculate_elements(pattern, &ways).await;
         info!("elements: {}", elements.len());
-        route.elements = elements;
+        pattern.elements = elements;
     }
 }
 
 #[tracing::instrument(skip_all)]
-async fn calculate_elements(route: &Route, ways: &WaysResponse) -> Vec<Element> {
-    let mut elements = Vec::new();
-    for (i, a) in route.stations.iter().enumerate() {
-        if i == route.stations.len() - 1 {
-            return elements;
-        }
-
-        let b = route.stations.get(i + 1).unwrap();
+async fn calculate_elements(pattern: &Pattern, ways: &WaysResponse) -> Vec<Element> {
+    let mut element_indices = BTreeSet::new();
+    for pair in pattern.stations.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
 
-        for element in &ways.elements {
+        for (i, element) in ways.elements.iter().enumerate() {
             if stations_inside_element(a, b, element) {
-                elements.push(element.clone());
+                element_indices.insert(i);
             }
         }
     }
 
-    elements
+    element_indices
+        .into_iter()
+        .map(|i| ways.elements[i].clone())
+        .collect()
 }
 
 fn stations_inside_element(a: &Station, b: &Station, element: &Element) -> bool {
@@ -265,7 +814,7 @@ struct BoundingBox {
     bottom_right: GeoPoint,
 }
 
-fn bounding_box(routes: &[Route]) -> BoundingBox {
+fn bounding_box(routes: &[&mut Route]) -> BoundingBox {
     let mut top = -85.;
     let mut bottom = 85.;
     let mut left = 180.0;
@@ -306,13 +855,40 @@ struct WaysResponse {
     elements: Vec<Element>,
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Element {
     bounds: Bounds,
     pub geometry: Vec<Point>,
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+impl Element {
+    fn from_shape(shape: &[&Shape]) -> Self {
+        let geometry: Vec<Point> = shape
+            .iter()
+            .map(|s| Point {
+                lat: s.shape_pt_lat,
+                lon: s.shape_pt_lon,
+            })
+            .collect();
+
+        let bounds = Bounds {
+            minlat: geometry.iter().map(|p| p.lat).fold(f64::INFINITY, f64::min),
+            minlon: geometry.iter().map(|p| p.lon).fold(f64::INFINITY, f64::min),
+            maxlat: geometry
+                .iter()
+                .map(|p| p.lat)
+                .fold(f64::NEG_INFINITY, f64::max),
+            maxlon: geometry
+                .iter()
+                .map(|p| p.lon)
+                .fold(f64::NEG_INFINITY, f64::max),
+        };
+
+        Self { bounds, geometry }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 struct Bounds {
     minlat: f64,
     minlon: f64,
@@ -320,15 +896,15 @@ struct Bounds {
     maxlon: f64,
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Point {
     pub lat: f64,
     pub lon: f64,
 }
 
 #[tracing::instrument]
-async fn load_ways(bounding_box: &BoundingBox) -> WaysResponse {
-    let client = ClientBuilder::new().build().unwrap();
+async fn load_ways(bounding_box: &BoundingBox) -> Result<WaysResponse, reqwest::Error> {
+    let client = ClientBuilder::new().build()?;
     let request = client
         .request(Method::POST, "https://overpass-api.de/api/interpreter")
         .body(format!(
@@ -338,8 +914,76 @@ async fn load_ways(bounding_box: &BoundingBox) -> WaysResponse {
             bounding_box.top_left.lat,
             bounding_box.bottom_right.lon,
         ))
-        .build()
-        .unwrap();
+        .build()?;
 
-    client.execute(request).await.unwrap().json().await.unwrap()
+    client.execute(request).await?.json().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::gtfs::tests::{date, read};
+
+    #[test]
+    fn shared_route_names_are_labelled_with_the_agency() {
+        let mut td = TransitData::default();
+        td.agencies.extend(
+            read(
+                "agency_id,agency_name,agency_url,agency_timezone\n\
+                 a,Stadtbus,https://a.example,Europe/Berlin\n\
+                 b,Regiobus,https://b.example,Europe/Berlin\n",
+            )
+            .0,
+        );
+        td.stops.extend(
+            read(
+                "stop_id,stop_name,stop_lat,stop_lon\n\
+                 x,X,49.0,8.4\n\
+                 y,Y,49.01,8.4\n",
+            )
+            .0,
+        );
+        td.routes.extend(
+            read(
+                "route_id,agency_id,route_short_name,route_type\n\
+                 r1,a,5,3\n\
+                 r2,b,5,3\n\
+                 r3,a,6,3\n",
+            )
+            .0,
+        );
+        td.trips.extend(
+            read(
+                "trip_id,route_id,service_id\n\
+                 t1,r1,s\n\
+                 t2,r2,s\n\
+                 t3,r3,s\n",
+            )
+            .0,
+        );
+        td.stop_times.extend(
+            read(
+                "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+                 t1,08:00:00,08:00:00,x,1\n\
+                 t1,08:10:00,08:10:00,y,2\n\
+                 t2,08:00:00,08:00:00,x,1\n\
+                 t2,08:10:00,08:10:00,y,2\n\
+                 t3,08:00:00,08:00:00,x,1\n\
+                 t3,08:10:00,08:10:00,y,2\n",
+            )
+            .0,
+        );
+        td.calendar_dates
+            .extend(read("service_id,date,exception_type\ns,20261019,1\n").0);
+        td.build_index();
+
+        let routes = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(load_routes(&td, date()));
+        let mut labels: Vec<&str> = routes.iter().map(|r| r.label.as_str()).collect();
+        labels.sort();
+
+        assert_eq!(labels, ["5 (Regiobus)", "5 (Stadtbus)", "6"]);
+    }
 }
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use crate::ui::{
+    gtfs::{GtfsTime, SECONDS_PER_DAY, TransitData},
+    state::Route,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DayType {
+    Weekday,
+    Saturday,
+    Sunday,
+}
+
+impl DayType {
+    pub const ALL: [DayType; 3] = [DayType::Weekday, DayType::Saturday, DayType::Sunday];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DayType::Weekday => "Monday - Friday",
+            DayType::Saturday => "Saturday",
+            DayType::Sunday => "Sunday",
+        }
+    }
+
+    fn of(date: NaiveDate) -> Self {
+        match date.weekday() {
+            Weekday::Sat => DayType::Saturday,
+            Weekday::Sun => DayType::Sunday,
+            _ => DayType::Weekday,
+        }
+    }
+
+    // The first day of this type on or after `from`.
+    fn date_from(&self, from: NaiveDate) -> NaiveDate {
+        from.iter_days().find(|d| DayType::of(*d) == *self).unwrap()
+    }
+}
+
+#[derive(Clone)]
+pub struct RouteStats {
+    pub route_name: String,
+    pub days: Vec<DayStats>,
+}
+
+#[derive(Clone)]
+pub struct DayStats {
+    pub day_type: DayType,
+    pub date: NaiveDate,
+    pub directions: Vec<DirectionStats>,
+}
+
+// Service of one direction, measured at the first stop of every trip.
+#[derive(Clone)]
+pub struct DirectionStats {
+    pub headsign: String,
+    pub trips: usize,
+    pub first: GtfsTime,
+    pub last: GtfsTime,
+    pub trips_per_hour: Vec<usize>,
+    // Between consecutive departures of the service day.
+    pub average_headway: Option<u32>,
+    pub max_headway: Option<u32>,
+    // The longest time without a departure, including the night.
+    pub longest_gap: u32,
+}
+
+impl RouteStats {
+    // Statistics for the first day of each type from `from` on.
+    pub fn new(td: &TransitData, route: &Route, from: NaiveDate) -> Self {
+        let trips: Vec<_> = td
+            .trips
+            .iter()
+            .filter(|t| t.route_id == route.route_id)
+            .collect();
+        let first_departures: HashMap<&str, GtfsTime> = trips
+            .iter()
+            .filter_map(|t| {
+                let departure = td
+                    .trip_stop_times(&t.trip_id)
+                    .into_iter()
+                    .find_map(|st| st.departure_time.or(st.arrival_time))?;
+                Some((t.trip_id.as_str(), departure))
+            })
+            .collect();
+
+        let days = DayType::ALL
+            .into_iter()
+            .map(|day_type| {
+                let date = day_type.date_from(from);
+                let service_day = td.service_day(date);
+
+                let mut departures: BTreeMap<Option<u8>, Vec<u32>> = BTreeMap::new();
+                for trip in trips.iter().filter(|t| service_day.runs(t)) {
+                    let Some(departure) = first_departures.get(trip.trip_id.as_str()) else {
+                        continue;
+                    };
+                    departures.entry(trip.direction_id).or_default().extend(
+                        td.trip_instances(&trip.trip_id)
+                            .iter()
+                            .filter_map(|i| i.apply(*departure))
+                            .map(|t| t.0),
+                    );
+                }
+
+                // Frequency based trips can leave a direction without departures.
+                let directions = departures
+                    .into_iter()
+                    .filter(|(_, departures)| !departures.is_empty())
+                    .map(|(direction_id, departures)| {
+                        let headsign = route
+                            .patterns
+                            .iter()
+                            .find(|p| p.direction_id == direction_id)
+                            .map(|p| p.headsign.clone())
+                            .unwrap_or_else(|| match direction_id {
+                                Some(id) => format!("Direction {id}"),
+                                None => route.name.clone(),
+                            });
+                        DirectionStats::new(headsign, departures)
+                    })
+                    .collect();
+
+                DayStats {
+                    day_type,
+                    date,
+                    directions,
+                }
+            })
+            .collect();
+
+        Self {
+            route_name: route.name.clone(),
+            days,
+        }
+    }
+}
+
+impl DirectionStats {
+    fn new(headsign: String, mut departures: Vec<u32>) -> Self {
+        departures.sort();
+
+        let first = departures[0];
+        let last = departures[departures.len() - 1];
+        // Trips after midnight of the service day count for the hours after 24:00.
+        let mut trips_per_hour = vec![0; 24.max(last as usize / 3600 + 1)];
+        for departure in &departures {
+            trips_per_hour[*departure as usize / 3600] += 1;
+        }
+
+        let headways: Vec<u32> = departures.windows(2).map(|w| w[1] - w[0]).collect();
+        let average_headway =
+            (!headways.is_empty()).then(|| headways.iter().sum::<u32>() / headways.len() as u32);
+        let max_headway = headways.iter().max().copied();
+        let night = (first + SECONDS_PER_DAY).saturating_sub(last);
+
+        Self {
+            headsign,
+            trips: departures.len(),
+            first: GtfsTime(first),
+            last: GtfsTime(last),
+            trips_per_hour,
+            average_headway,
+            max_headway,
+            longest_gap: max_headway.unwrap_or_default().max(night),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::*;
+    use crate::ui::gtfs::{
+        Mode,
+        tests::{date, small_feed},
+    };
+
+    fn route() -> Route {
+        Route {
+            stations: Vec::new(),
+            patterns: Vec::new(),
+            route_id: "r".to_string(),
+            name: "5".to_string(),
+            label: "5".to_string(),
+            agency_id: String::new(),
+            mode: Mode::Tram,
+            color: Color32::WHITE,
+            text_color: Color32::BLACK,
+            trip_count: 4,
+            headway: None,
+            markers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn headways_are_measured_at_the_first_stop() {
+        let mut td = small_feed(
+            "trip_id,route_id,service_id,direction_id\n\
+             t1,r,x,0\n\
+             t2,r,x,0\n\
+             t3,r,x,0\n\
+             t4,r,x,0\n",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             t1,06:00:00,06:00:00,a,1\n\
+             t1,06:10:00,06:10:00,b,2\n\
+             t2,06:10:00,06:10:00,a,1\n\
+             t2,06:20:00,06:20:00,b,2\n\
+             t3,06:40:00,06:40:00,a,1\n\
+             t3,06:50:00,06:50:00,b,2\n\
+             t4,07:00:00,07:00:00,a,1\n\
+             t4,07:10:00,07:10:00,b,2\n",
+        );
+        td.build_index();
+
+        let stats = RouteStats::new(&td, &route(), date());
+        let weekday = &stats.days[0];
+        assert_eq!(weekday.day_type, DayType::Weekday);
+        assert_eq!(weekday.date, date());
+
+        let direction = &weekday.directions[0];
+        assert_eq!(direction.headsign, "Direction 0");
+        assert_eq!(direction.trips, 4);
+        assert_eq!(direction.first, GtfsTime::hms(6, 0, 0));
+        assert_eq!(direction.last, GtfsTime::hms(7, 0, 0));
+        assert_eq!(direction.trips_per_hour[6..8], [3, 1]);
+        assert_eq!(direction.average_headway, Some(1200));
+        assert_eq!(direction.max_headway, Some(1800));
+        // From the last departure to the first one of the next day.
+        assert_eq!(direction.longest_gap, 23 * 3600);
+
+        // The trips do not run on the weekend.
+        assert!(stats.days[1].directions.is_empty());
+        assert!(stats.days[2].directions.is_empty());
+    }
+}
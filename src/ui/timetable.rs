@@ -0,0 +1,71 @@
+use chrono::NaiveDate;
+
+use crate::ui::{
+    gtfs::{GtfsTime, TransitData},
+    state::{Pattern, Route, Station},
+};
+
+// Timetable of a route pattern on one service date, with a column per trip.
+#[derive(Clone)]
+pub struct Timetable {
+    pub title: String,
+    pub date: NaiveDate,
+    pub stations: Vec<Station>,
+    pub trips: Vec<TimetableTrip>,
+}
+
+#[derive(Clone)]
+pub struct TimetableTrip {
+    pub trip_id: String,
+    pub accessible: bool,
+    // Departure at each station of the timetable, if the stop has a time.
+    pub times: Vec<Option<GtfsTime>>,
+}
+
+impl Timetable {
+    pub fn new(td: &TransitData, route: &Route, pattern: &Pattern, date: NaiveDate) -> Self {
+        let mut trips = Vec::new();
+        for trip_id in &pattern.trip_ids {
+            let trip_stop_times = td.trip_stop_times(trip_id);
+            if trip_stop_times.is_empty() {
+                continue;
+            }
+
+            // Consecutive stops at the same station are merged like the stations
+            // of the pattern, keeping the departure from the station.
+            let mut times: Vec<(&str, Option<GtfsTime>)> = Vec::new();
+            for stop_time in trip_stop_times {
+                let Some(station) = td.station(&stop_time.stop_id) else {
+                    continue;
+                };
+
+                let time = stop_time.departure_time.or(stop_time.arrival_time);
+                match times.last_mut() {
+                    Some((id, last)) if *id == station.stop_id => *last = time.or(*last),
+                    _ => times.push((&station.stop_id, time)),
+                }
+            }
+
+            let accessible = td.trip(trip_id).is_some_and(|t| t.is_accessible());
+            for instance in td.trip_instances(trip_id) {
+                trips.push(TimetableTrip {
+                    trip_id: trip_id.clone(),
+                    accessible,
+                    times: times
+                        .iter()
+                        .map(|(_, time)| time.and_then(|t| instance.apply(t)))
+                        .collect(),
+                });
+            }
+        }
+
+        trips.sort_by_key(|t| t.times.iter().flatten().next().copied());
+
+        Self {
+            title: format!("{} → {}", route.name, pattern.headsign),
+            date,
+            stations: pattern.stations.clone(),
+            trips,
+        }
+    }
+}